@@ -0,0 +1,155 @@
+// Airing schedule of the shows on the user's list
+// Shows when the next episode of everything still releasing comes out, or exports it as a calendar
+
+// =============== Imports ================
+use crate::api::anilist::user_fetch;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use console::style;
+use reqwest::Client;
+use std::fs;
+use std::path::Path;
+
+pub async fn show(client: &Client) -> Result<()> {
+    let entries = user_fetch::airing(client).await?;
+    if entries.is_empty() {
+        println!("Nothing on your list is airing right now.");
+        return Ok(());
+    }
+
+    println!("{}", style("Airing").bold().underlined());
+    let width = entries
+        .iter()
+        .map(|e| e.title.chars().count())
+        .max()
+        .unwrap_or(0);
+    for entry in &entries {
+        let unwatched = match entry.unwatched() {
+            0 => style("caught up".to_string()).dim(),
+            n => style(format!("{} unwatched", n)).green(),
+        };
+        println!(
+            "{:<width$} | episode {:>3} | {} (in {}) | {}",
+            entry.title,
+            entry.next.episode,
+            entry.next.local_time(),
+            entry.next.countdown(),
+            unwatched,
+            width = width
+        );
+    }
+    Ok(())
+}
+
+// =============== Calendar ===============
+// Writes every upcoming episode as an iCalendar file
+// UIDs only depend on the anime and the episode, so importing it again updates the events
+pub async fn export_ics(client: &Client, path: &Path) -> Result<()> {
+    let schedules = user_fetch::schedules(client).await?;
+    let stamp = ics_time(Utc::now().timestamp());
+
+    let mut lines: Vec<String> = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//yato//Airing schedule//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "X-WR-CALNAME:Airing anime".to_string(),
+    ];
+    let mut count = 0;
+    for schedule in &schedules {
+        let url = format!("https://anilist.co/anime/{}", schedule.media_id);
+        for episode in &schedule.episodes {
+            let end = episode.airing_at + i64::from(schedule.duration) * 60;
+            lines.extend([
+                "BEGIN:VEVENT".to_string(),
+                format!("UID:yato-{}-{}@anilist.co", schedule.media_id, episode.episode),
+                format!("DTSTAMP:{}", stamp),
+                format!("DTSTART:{}", ics_time(episode.airing_at)),
+                format!("DTEND:{}", ics_time(end)),
+                format!("SUMMARY:{}", escape(&format!("{} - Episode {}", schedule.title, episode.episode))),
+                format!("DESCRIPTION:{}", escape(&format!("Episode {} of {}\n{}", episode.episode, schedule.title, url))),
+                format!("URL:{}", url),
+                "END:VEVENT".to_string(),
+            ]);
+            count += 1;
+        }
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    let content: String = lines.iter().map(|line| fold(line)).collect();
+    fs::write(path, content).with_context(|| format!("Failed to write calendar: {:?}", path))?;
+    log::info!("Exported {} episodes to {:?}", count, path);
+    println!("Exported {} upcoming episodes to {}", count, path.display());
+    Ok(())
+}
+
+fn ics_time(timestamp: i64) -> String {
+    DateTime::<Utc>::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+// Text values can't have raw commas, semicolons or new lines
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+// Lines longer than 75 bytes are folded, continuation lines start with a space
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_special_characters() {
+        assert_eq!(escape("a;b,c"), "a\\;b\\,c");
+        assert_eq!(escape("back\\slash"), "back\\\\slash");
+        assert_eq!(escape("two\nlines"), "two\\nlines");
+    }
+
+    #[test]
+    fn short_lines_are_not_folded() {
+        assert_eq!(fold("SUMMARY:Episode 1"), "SUMMARY:Episode 1\r\n");
+    }
+
+    #[test]
+    fn long_lines_are_folded_at_75_bytes() {
+        let line = "x".repeat(160);
+        let folded = fold(&line);
+        let lines: Vec<&str> = folded.trim_end_matches("\r\n").split("\r\n").collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|l| l.len() <= 75));
+        assert!(lines[1..].iter().all(|l| l.starts_with(' ')));
+        let unfolded: String = lines.iter().map(|l| l.strip_prefix(' ').unwrap_or(l)).collect();
+        assert_eq!(unfolded, line);
+    }
+
+    #[test]
+    fn folding_never_splits_a_character() {
+        let line = "é".repeat(80);
+        let folded = fold(&line);
+        for part in folded.split("\r\n") {
+            assert!(part.len() <= 75);
+        }
+        assert_eq!(folded.replace("\r\n ", "").trim_end(), line);
+    }
+}
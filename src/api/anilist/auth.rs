@@ -0,0 +1,516 @@
+// Logging in to AniList and keeping the token safe
+
+// =============== Imports ================
+use crate::config;
+use crate::profile;
+use crate::theme;
+use crate::utils;
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use dialoguer::Input;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{self, Stdio};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{Duration, timeout};
+
+// Constant variables
+// ! AniList only redirects to the URL registered for the client, so the browser login needs
+// ! a client of the user's own with http://localhost:<login_redirect_port>/callback, set in the config
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(120);
+const TOKEN_ENV: &str = "YATO_ANILIST_TOKEN";
+// Warn the user this long before the token runs out
+const EXPIRY_WARNING: u64 = 7 * 24 * 60 * 60;
+
+
+// * The redirect puts the token in the url fragment, which never reaches the server
+// * so this page sends it back to the listener
+const CALLBACK_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>yato</title></head>
+<body style="font-family: sans-serif; text-align: center; margin-top: 20vh;">
+<p id="message">Logging in...</p>
+<script>
+const params = window.location.hash.substring(1) || window.location.search.substring(1);
+const message = document.getElementById("message");
+fetch("/token?" + params)
+    .then((res) => {
+        message.innerText = res.ok
+            ? "Logged in to yato, you can close this tab now."
+            : "Login failed, check your terminal.";
+    })
+    .catch(() => { message.innerText = "Login failed, check your terminal."; });
+</script>
+</body>
+</html>"#;
+
+#[derive(Serialize, Deserialize)]
+struct StoredToken {
+    access_token: String,
+    // Unix timestamp, None if we couldn't figure it out
+    expires_at: Option<u64>,
+}
+
+impl StoredToken {
+    fn new(access_token: String, expires_in: Option<u64>) -> Self {
+        let expires_at = expires_in
+            .map(|seconds| utils::now() + seconds)
+            .or_else(|| jwt_expiry(&access_token));
+        Self {
+            access_token,
+            expires_at,
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|at| at <= utils::now())
+    }
+}
+
+// Returns a usable token, logging in if there is none or it expired
+pub async fn token() -> Result<String> {
+    if let Some(token) = env_token() {
+        return Ok(token);
+    }
+
+    match load() {
+        Some(stored) if stored.is_expired() => {
+            log::warn!("AniList token expired");
+            println!("Your AniList token has expired, please log in again.");
+            logout()?;
+        }
+        Some(stored) => {
+            if let Some(left) = stored.expires_at.map(|at| at.saturating_sub(utils::now()))
+                && left < EXPIRY_WARNING
+            {
+                log::warn!("AniList token expires in {} seconds", left);
+                eprintln!(
+                    "Your AniList token expires in {} day(s), run yato --login to renew it.",
+                    left / (24 * 60 * 60)
+                );
+            }
+            return Ok(stored.access_token);
+        }
+        None => {}
+    }
+
+    login().await
+}
+
+// The token if the user is logged in, never asks to log in
+pub fn existing_token() -> Option<String> {
+    env_token().or_else(|| {
+        load()
+            .filter(|stored| !stored.is_expired())
+            .map(|stored| stored.access_token)
+    })
+}
+
+// True if the token comes from the environment, that one is never stored or deleted
+pub fn from_env() -> bool {
+    env_token().is_some()
+}
+
+fn env_token() -> Option<String> {
+    std::env::var(TOKEN_ENV)
+        .ok()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+}
+
+// Gets a new token through the browser if a redirect port is set, by pasting it in otherwise
+pub async fn login() -> Result<String> {
+    log::info!("Starting AniList login");
+    let config = config::load_config();
+    let client_id = config.anilist_client_id;
+    let token = match config.login_redirect_port {
+        0 => None,
+        port => browser_login(&client_id, port).await,
+    };
+
+    let token = match token {
+        Some(token) => token,
+        None => StoredToken::new(ask_for_token(&client_id)?, None),
+    };
+
+    save(&token)?;
+    log::info!("AniList token saved");
+    Ok(token.access_token)
+}
+
+// Catches the token on the redirect, None if the user has to paste it in
+async fn browser_login(client_id: &str, port: u16) -> Option<StoredToken> {
+    match TcpListener::bind(("127.0.0.1", port)).await {
+        Ok(listener) => {
+            let state = nonce();
+            let url = format!(
+                "https://anilist.co/api/v2/oauth/authorize?client_id={}&response_type=token&state={}",
+                client_id, state
+            );
+            println!("Log in to AniList in your browser:\n{}", url);
+            open_browser(&url);
+
+            match timeout(LOGIN_TIMEOUT, wait_for_redirect(listener, &state)).await {
+                Ok(Ok(token)) => Some(token),
+                Ok(Err(e)) => {
+                    log::error!("Browser login failed: {}", e);
+                    eprintln!("Browser login failed: {}", e);
+                    None
+                }
+                Err(_) => {
+                    log::warn!("No redirect arrived in {:?}", LOGIN_TIMEOUT);
+                    println!("The browser didn't redirect back in time.");
+                    None
+                }
+            }
+        }
+        Err(e) => {
+            log::warn!("Can't listen on port {}: {}", port, e);
+            println!("Port {} is in use, paste the token instead.", port);
+            None
+        }
+    }
+}
+
+// Removes the stored token from everywhere
+pub fn logout() -> Result<()> {
+    let path = token_path()?;
+    if path.exists() {
+        fs::remove_file(&path)
+            .with_context(|| format!("Failed to remove token file: {:?}", path))?;
+    }
+    secret_clear();
+    log::info!("AniList token removed");
+    Ok(())
+}
+
+fn ask_for_token(client_id: &str) -> Result<String> {
+    let prompt = format!(
+        "Please enter your Anilist access token generated here:\nhttps://anilist.co/api/v2/oauth/authorize?client_id={}&response_type=token",
+        client_id
+    );
+    let token: String = Input::with_theme(&theme::CustomTheme {})
+        .with_prompt(prompt)
+        .interact()?;
+    let token = token.trim().to_string();
+    if token.is_empty() {
+        println!("No token was given.");
+        process::exit(1);
+    }
+    Ok(token)
+}
+
+// =============== Redirect listener ===============
+// Random value sent with the login and expected back, so other pages can't hand us a token
+fn nonce() -> String {
+    // * Every RandomState is seeded differently, which is random enough for this
+    let part = || {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(utils::now());
+        hasher.finish()
+    };
+    format!("{:016x}{:016x}", part(), part())
+}
+
+async fn wait_for_redirect(listener: TcpListener, state: &str) -> Result<StoredToken> {
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let mut buf = vec![0u8; 8192];
+        let n = stream.read(&mut buf).await?;
+        let request = String::from_utf8_lossy(&buf[..n]);
+
+        // First line looks like: GET /token?access_token=...&expires_in=... HTTP/1.1
+        let target = request
+            .lines()
+            .next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .unwrap_or("/");
+        let (route, query) = target.split_once('?').unwrap_or((target, ""));
+
+        match route {
+            "/callback" => respond(&mut stream, "200 OK", "text/html", CALLBACK_PAGE).await?,
+            "/token" => {
+                let params = parse_query(query);
+                if params.get("state").map(String::as_str) != Some(state) {
+                    log::warn!("Ignoring a login redirect with the wrong state");
+                    respond(&mut stream, "400 Bad Request", "text/plain", "wrong state").await?;
+                    continue;
+                }
+                if let Some(token) = params.get("access_token") {
+                    respond(&mut stream, "200 OK", "text/plain", "ok").await?;
+                    let expires_in = params.get("expires_in").and_then(|s| s.parse().ok());
+                    return Ok(StoredToken::new(token.clone(), expires_in));
+                }
+                respond(&mut stream, "400 Bad Request", "text/plain", "no token").await?;
+                if let Some(error) = params.get("error") {
+                    let description = params.get("error_description").unwrap_or(error);
+                    return Err(anyhow::anyhow!("AniList refused the login: {}", description));
+                }
+            }
+            _ => respond(&mut stream, "404 Not Found", "text/plain", "not found").await?,
+        }
+    }
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &str) -> Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| {
+            let value = urlencoding::decode(value)
+                .map(|v| v.into_owned())
+                .unwrap_or_else(|_| value.to_string());
+            (key.to_string(), value)
+        })
+        .collect()
+}
+
+fn open_browser(url: &str) {
+    let result = if cfg!(target_os = "macos") {
+        process::Command::new("open").arg(url).stdout(Stdio::null()).stderr(Stdio::null()).spawn()
+    } else if cfg!(target_os = "windows") {
+        process::Command::new("cmd").args(["/C", "start", "", url]).spawn()
+    } else {
+        process::Command::new("xdg-open").arg(url).stdout(Stdio::null()).stderr(Stdio::null()).spawn()
+    };
+    if let Err(e) = result {
+        log::warn!("Failed to open browser: {}", e);
+    }
+}
+
+// =============== Storage ===============
+// Every profile has its own token
+fn token_path() -> Result<PathBuf> {
+    Ok(profile::data_dir()?.join("anilist_token"))
+}
+
+fn load() -> Option<StoredToken> {
+    if let Some(secret) = secret_lookup() {
+        return parse_stored(&secret);
+    }
+
+    let path = token_path().ok()?;
+    let contents = fs::read_to_string(&path).ok()?;
+    let stored = parse_stored(&contents)?;
+
+    // * Old versions saved the bare token with default permissions, so it gets saved again
+    if serde_json::from_str::<StoredToken>(&contents).is_err() {
+        log::info!("Migrating plain text AniList token");
+        if let Err(e) = save(&stored) {
+            log::warn!("Failed to migrate token file: {}", e);
+        }
+    }
+    Some(stored)
+}
+
+// Reads both the current format and the old plain text one
+fn parse_stored(contents: &str) -> Option<StoredToken> {
+    if let Ok(stored) = serde_json::from_str::<StoredToken>(contents) {
+        return Some(stored);
+    }
+    let token = contents.trim();
+    if token.is_empty() {
+        return None;
+    }
+    Some(StoredToken::new(token.to_string(), None))
+}
+
+// Secret Service first, file with owner only permissions if that's not available
+fn save(token: &StoredToken) -> Result<()> {
+    let json = serde_json::to_string(token)?;
+    let path = token_path()?;
+
+    if secret_store(&json) {
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+        log::info!("AniList token stored in the Secret Service");
+        return Ok(());
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(&path)
+        .with_context(|| format!("Failed to open token file: {:?}", path))?;
+    // The mode only counts when the file is created, older files need it set by hand
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(json.as_bytes())
+        .with_context(|| format!("Failed to write token file: {:?}", path))?;
+    Ok(())
+}
+
+// =============== Secret Service ===============
+// * Talks to the Secret Service (GNOME Keyring, KWallet) through secret-tool from libsecret
+// * If it's not installed all of these do nothing
+
+// Attributes of the Secret Service entry, the default profile keeps the plain name
+fn secret_attributes() -> [String; 4] {
+    let account = if profile::is_default() {
+        "anilist".to_string()
+    } else {
+        format!("anilist:{}", profile::active())
+    };
+    ["service".to_string(), "yato".to_string(), "account".to_string(), account]
+}
+
+fn secret_lookup() -> Option<String> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let output = process::Command::new("secret-tool")
+        .arg("lookup")
+        .args(secret_attributes())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let secret = String::from_utf8(output.stdout).ok()?;
+    (output.status.success() && !secret.trim().is_empty()).then_some(secret)
+}
+
+fn secret_store(secret: &str) -> bool {
+    if !cfg!(target_os = "linux") {
+        return false;
+    }
+    let child = process::Command::new("secret-tool")
+        .args(["store", "--label=yato AniList token"])
+        .args(secret_attributes())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    let Ok(mut child) = child else {
+        return false;
+    };
+    if let Some(mut stdin) = child.stdin.take()
+        && stdin.write_all(secret.as_bytes()).is_err()
+    {
+        return false;
+    }
+    child.wait().is_ok_and(|status| status.success())
+}
+
+fn secret_clear() {
+    if !cfg!(target_os = "linux") {
+        return;
+    }
+    let _ = process::Command::new("secret-tool")
+        .arg("clear")
+        .args(secret_attributes())
+        .stderr(Stdio::null())
+        .status();
+}
+
+// =============== Expiry ===============
+// AniList tokens are JWTs, the expiry is in the "exp" claim of the payload
+fn jwt_expiry(token: &str) -> Option<u64> {
+    #[derive(Deserialize)]
+    struct Claims {
+        exp: Option<f64>,
+    }
+    let payload = token.split('.').nth(1)?;
+    let payload = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    let claims: Claims = serde_json::from_slice(&payload).ok()?;
+    claims.exp.map(|exp| exp as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::engine::general_purpose::URL_SAFE;
+
+    fn jwt(claims: &str) -> String {
+        format!("{}.{}.signature", URL_SAFE_NO_PAD.encode("{}"), URL_SAFE_NO_PAD.encode(claims))
+    }
+
+    #[test]
+    fn parses_redirect_queries() {
+        let params = parse_query("access_token=abc.def&token_type=Bearer&expires_in=31536000&state=x1");
+        assert_eq!(params["access_token"], "abc.def");
+        assert_eq!(params["expires_in"], "31536000");
+        assert_eq!(params["state"], "x1");
+        assert_eq!(params.len(), 4);
+    }
+
+    #[test]
+    fn decodes_query_values_and_skips_broken_pairs() {
+        let params = parse_query("error=access_denied&error_description=The%20user%20denied&broken");
+        assert_eq!(params["error_description"], "The user denied");
+        assert!(!params.contains_key("broken"));
+        assert!(parse_query("").is_empty());
+    }
+
+    #[test]
+    fn reads_the_expiry_of_a_jwt() {
+        assert_eq!(jwt_expiry(&jwt(r#"{"exp":1893456000}"#)), Some(1893456000));
+        assert_eq!(jwt_expiry(&jwt(r#"{"exp":1893456000.5}"#)), Some(1893456000));
+        assert_eq!(jwt_expiry(&jwt(r#"{"sub":"1"}"#)), None);
+    }
+
+    #[test]
+    fn padded_payloads_are_read_too() {
+        // * 20 bytes of claims need padding with the standard engine
+        let claims = r#"{"exp":1893456000}  "#;
+        let token = format!("x.{}.y", URL_SAFE.encode(claims));
+        assert!(token.contains('='));
+        assert_eq!(jwt_expiry(&token), Some(1893456000));
+    }
+
+    #[test]
+    fn broken_tokens_have_no_expiry() {
+        assert_eq!(jwt_expiry("not a jwt"), None);
+        assert_eq!(jwt_expiry("a.!!!.c"), None);
+        assert_eq!(jwt_expiry(&format!("a.{}.c", URL_SAFE_NO_PAD.encode("not json"))), None);
+    }
+
+    #[test]
+    fn reads_stored_and_plain_tokens() {
+        let stored = parse_stored(r#"{"access_token":"abc","expires_at":100}"#).unwrap();
+        assert_eq!(stored.access_token, "abc");
+        assert!(stored.is_expired());
+
+        let plain = parse_stored(&format!(" {}\n", jwt(r#"{"exp":4102444800}"#))).unwrap();
+        assert_eq!(plain.expires_at, Some(4102444800));
+        assert!(!plain.is_expired());
+        assert!(parse_stored("  \n").is_none());
+    }
+
+    #[test]
+    fn nonces_differ() {
+        assert_ne!(nonce(), nonce());
+        assert_eq!(nonce().len(), 32);
+    }
+}
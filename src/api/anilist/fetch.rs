@@ -1,113 +1,608 @@
 // Getting information from Anilist
 
 // =============== Imports ================
+use crate::api::anilist::graphql::{self, CoverImage, FuzzyDate, NextAiring, Title};
+use crate::api::anilist::{mutation, score};
 use crate::config;
+use crate::cover;
 use crate::theme;
 use crate::utils;
 
 use log;
 use console::{self, Style};
-use dialoguer::FuzzySelect;
+use dialoguer::{Input, Select};
 use reqwest::Client;
-use serde_json::{Value, json};
-use anyhow::Result;
+use serde::Deserialize;
+use serde_json::json;
+use anyhow::{Context, Result};
+use futures::future::join_all;
 
-// Constant variables
-const ANILIST_API_URL: &str = "https://graphql.anilist.co";
-// const ANILIST_CLIENT_ID: &str = "25501";
+// =============== Search ===============
+const PER_PAGE: u32 = 20;
+const FORMATS: [&str; 7] = ["TV", "TV_SHORT", "MOVIE", "SPECIAL", "OVA", "ONA", "MUSIC"];
+const SEASONS: [&str; 4] = ["WINTER", "SPRING", "SUMMER", "FALL"];
+const MEDIA_STATUSES: [&str; 5] = ["FINISHED", "RELEASING", "NOT_YET_RELEASED", "CANCELLED", "HIATUS"];
+
+#[derive(Deserialize)]
+struct SearchData {
+    #[serde(rename = "Page")]
+    page: SearchPage,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchPage {
+    page_info: PageInfo,
+    media: Vec<SearchMedia>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchMedia {
+    id: i32,
+    title: Title,
+    format: Option<String>,
+    season_year: Option<i32>,
+    start_date: FuzzyDate,
+    episodes: Option<u32>,
+    average_score: Option<u32>,
+    cover_image: Option<CoverImage>,
+}
+
+impl SearchMedia {
+    // Enough to tell same-named shows and movies apart
+    fn row(&self) -> String {
+        let year = self
+            .season_year
+            .map(|y| y.to_string())
+            .or_else(|| self.start_date.year.map(|y| y.to_string()))
+            .unwrap_or_else(|| "?".to_string());
+        let episodes = self
+            .episodes
+            .map(|e| format!("{} ep", e))
+            .unwrap_or_else(|| "? ep".to_string());
+        let score = utils::score(self.average_score);
+        format!(
+            "{} | {} | {} | {} | {}",
+            self.title.preferred(),
+            self.format.as_deref().unwrap_or("?"),
+            year,
+            episodes,
+            score
+        )
+    }
+}
+
+#[derive(Deserialize)]
+struct GenreData {
+    #[serde(rename = "GenreCollection")]
+    genres: Vec<String>,
+}
+
+// Filters of the search, None means it's not filtered
+#[derive(Default)]
+struct SearchFilters {
+    format: Option<String>,
+    year: Option<i32>,
+    season: Option<String>,
+    status: Option<String>,
+    genre: Option<String>,
+    tag: Option<String>,
+}
+
+impl SearchFilters {
+    fn summary(&self) -> String {
+        let set: Vec<String> = [
+            self.format.clone(),
+            self.year.map(|y| y.to_string()),
+            self.season.clone(),
+            self.status.clone(),
+            self.genre.clone(),
+            self.tag.clone(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if set.is_empty() {
+            "none".to_string()
+        } else {
+            set.join(", ")
+        }
+    }
+}
 
 // Searches by name, returns anilist id if found (and selected)
+// Results can be filtered and paged through, an empty name searches by the filters only
 pub async fn search(client: &Client, input: String) -> Result<i32> {
     log::info!("Searching AniList for: {}", input);
-    let adult = config::load_config().show_adult_content;
+    let mut filters = SearchFilters::default();
+    let mut page = 1;
+
+    loop {
+        let (media, has_next_page) = search_page(client, &input, &filters, page).await?;
+
+        // * The first rows are for changing the search, the rest are results
+        let mut options = vec![format!("[Filters: {}]", filters.summary())];
+        if page > 1 {
+            options.push(format!("[Previous page ({})]", page - 1));
+        }
+        if has_next_page {
+            options.push(format!("[Next page ({})]", page + 1));
+        }
+        let controls = options.len();
+        if media.is_empty() {
+            options.push("No results found".to_string());
+        }
+        options.extend(media.iter().map(SearchMedia::row));
+        let mut covers = vec![None; options.len() - media.len()];
+        covers.extend(media.iter().map(|m| m.cover_image.clone().and_then(|c| c.medium)));
+
+        let selected_index = cover::select(
+            client,
+            &format!("Choose an anime (page {}):", page),
+            &options,
+            &covers,
+            controls.min(options.len() - 1),
+        )
+        .await?;
+        utils::clear();
+
+        let Some(index) = selected_index else {
+            return Err(anyhow::anyhow!("No selection was made"));
+        };
+        if index >= controls {
+            if let Some(anime) = media.get(index - controls) {
+                log::info!("AniList search completed successfully");
+                return Ok(anime.id);
+            }
+            continue;
+        }
+        match options[index].as_str() {
+            option if option.starts_with("[Filters") => {
+                edit_filters(client, &mut filters).await?;
+                page = 1;
+            }
+            option if option.starts_with("[Previous") => page -= 1,
+            _ => page += 1,
+        }
+    }
+}
+
+// The isAdult filter for queries, leaving it out shows both and false hides adult anime
+fn is_adult() -> Option<bool> {
+    (!config::load_config().show_adult_content).then_some(false)
+}
+
+async fn search_page(
+    client: &Client,
+    input: &str,
+    filters: &SearchFilters,
+    page: u32,
+) -> Result<(Vec<SearchMedia>, bool)> {
+    let is_adult = is_adult();
+    let search = (!input.trim().is_empty()).then_some(input.trim());
+    let sort = if search.is_some() { "SEARCH_MATCH" } else { "POPULARITY_DESC" };
 
     let query_string = r#"
-        query ($search: String, $isAdult: Boolean) {
-            Page {
-                media(search: $search, type: ANIME, isAdult: $isAdult) {
+        query ($search: String, $isAdult: Boolean, $page: Int, $perPage: Int, $sort: [MediaSort],
+               $format: MediaFormat, $seasonYear: Int, $season: MediaSeason, $status: MediaStatus,
+               $genre: String, $tag: String) {
+            Page(page: $page, perPage: $perPage) {
+                pageInfo {
+                    hasNextPage
+                }
+                media(search: $search, type: ANIME, isAdult: $isAdult, sort: $sort, format: $format,
+                      seasonYear: $seasonYear, season: $season, status: $status, genre: $genre, tag: $tag) {
                     id
                     title {
                         romaji
                         english
+                        native
+                    }
+                    format
+                    seasonYear
+                    startDate {
+                        year
+                        month
+                        day
+                    }
+                    episodes
+                    averageScore
+                    coverImage {
+                        medium
                     }
                 }
             }
         }
     "#;
 
-    let variables = json!({ "search": input, "isAdult": adult });
-
-    let response = client
-        .post(ANILIST_API_URL)
-        .header("Content-Type", "application/json")
-        .json(&json!({
-            "query": query_string,
-            "variables": variables
-        }))
-        .send()
-        .await;
-
-    match response {
-        Ok(res) => {
-            if res.status().is_success() {
-                let data: serde_json::Value = res.json().await.expect("Failed to parse response");
-                let anime_list = data["data"]["Page"]["media"]
-                    .as_array()
-                    .expect("Expected 'media' to be an array");
-
-                if anime_list.is_empty() {
-                    return Err(anyhow::anyhow!("No results found"));
-                }
+    let variables = json!({
+        "search": search,
+        "isAdult": is_adult,
+        "page": page,
+        "perPage": PER_PAGE,
+        "sort": [sort],
+        "format": filters.format,
+        "seasonYear": filters.year,
+        "season": filters.season,
+        "status": filters.status,
+        "genre": filters.genre,
+        "tag": filters.tag,
+    });
+
+    let data: SearchData = graphql::query(client, query_string, variables)
+        .await
+        .map_err(|e| {
+            eprintln!("Error sending request: {}", e);
+            log::error!("Request to AniList API failed: {}", e);
+            e
+        })?;
+    Ok((data.page.media, data.page.page_info.has_next_page))
+}
+
+async fn genres(client: &Client) -> Result<Vec<String>> {
+    let query_string = r#"
+        query {
+            GenreCollection
+        }
+    "#;
+    let data: GenreData = graphql::query(client, query_string, json!({}))
+        .await
+        .with_context(|| "Failed to get the genres")?;
+    Ok(data.genres)
+}
 
-                let options: Vec<String> = anime_list
-                    .iter()
-                    .map(|anime| {
-                        anime["title"]["english"]
-                            .as_str()
-                            .unwrap_or(anime["title"]["romaji"].as_str().unwrap_or("Unknown Title"))
-                            .to_string()
-                    })
-                    .collect();
-
-                let theme = theme::CustomTheme {};
-
-                let selected_index = FuzzySelect::with_theme(&theme)
-                    .with_prompt("Choose an anime:")
-                    .items(&options)
-                    .default(0)
-                    .clear(true)
-                    .interact_opt()?;
+async fn edit_filters(client: &Client, filters: &mut SearchFilters) -> Result<()> {
+    let theme = theme::CustomTheme {};
+    let to_strings = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<String>>();
+    loop {
+        let any = |value: &Option<String>| value.clone().unwrap_or_else(|| "Any".to_string());
+        let options = vec![
+            format!("Format: {}", any(&filters.format)),
+            format!("Year: {}", any(&filters.year.map(|y| y.to_string()))),
+            format!("Season: {}", any(&filters.season)),
+            format!("Status: {}", any(&filters.status)),
+            format!("Genre: {}", any(&filters.genre)),
+            format!("Tag: {}", any(&filters.tag)),
+            "Clear filters".to_string(),
+            "Search".to_string(),
+        ];
+        let selection = Select::with_theme(&theme)
+            .with_prompt("Search filters")
+            .items(&options)
+            .default(options.len() - 1)
+            .interact_opt()?;
+        utils::clear();
 
+        match selection {
+            Some(0) => {
+                if let Some(format) = utils::pick("Format", &to_strings(&FORMATS))? {
+                    filters.format = format;
+                }
+            }
+            Some(1) => {
+                let input: String = Input::with_theme(&theme)
+                    .with_prompt("Year (empty for any)")
+                    .allow_empty(true)
+                    .interact_text()?;
+                filters.year = input.trim().parse().ok();
                 utils::clear();
-                if let Some(index) = selected_index {
-                    log::info!("AniList search completed successfully");
-                    Ok(anime_list[index]["id"].as_i64().expect("No ID found") as i32)
-                } else {
-                    Err(anyhow::anyhow!("No selection was made"))
+            }
+            Some(2) => {
+                if let Some(season) = utils::pick("Season", &to_strings(&SEASONS))? {
+                    filters.season = season;
+                }
+            }
+            Some(3) => {
+                if let Some(status) = utils::pick("Status", &to_strings(&MEDIA_STATUSES))? {
+                    filters.status = status;
+                }
+            }
+            Some(4) => {
+                if let Some(genre) = utils::pick("Genre", &genres(client).await?)? {
+                    filters.genre = genre;
+                }
+            }
+            Some(5) => {
+                let input: String = Input::with_theme(&theme)
+                    .with_prompt("Tag, e.g. Time Skip (empty for any)")
+                    .allow_empty(true)
+                    .interact_text()?;
+                let tag = input.trim();
+                filters.tag = (!tag.is_empty()).then(|| tag.to_string());
+                utils::clear();
+            }
+            Some(6) => *filters = SearchFilters::default(),
+            _ => return Ok(()),
+        }
+    }
+}
+
+// =============== Season ===============
+#[derive(Deserialize)]
+struct SeasonData {
+    #[serde(rename = "Page")]
+    page: SeasonPage,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SeasonPage {
+    page_info: PageInfo,
+    media: Vec<SeasonMedia>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+    has_next_page: bool,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SeasonMedia {
+    pub id: i32,
+    pub title: Title,
+    pub format: Option<String>,
+    #[serde(default)]
+    pub genres: Vec<String>,
+    pub episodes: Option<u32>,
+    pub average_score: Option<u32>,
+    pub popularity: Option<u32>,
+    pub start_date: FuzzyDate,
+    pub cover_image: Option<CoverImage>,
+    pub media_list_entry: Option<ListStatus>, // The user's entry, if it's on their list
+}
+
+// The user's list entry of an anime, as much as the browsers need
+#[derive(Deserialize, Clone)]
+pub struct ListStatus {
+    pub status: Option<String>,
+    pub progress: Option<u32>,
+}
+
+// Every anime of a season, e.g. ("FALL", 2025)
+pub async fn season(client: &Client, season: &str, year: i32) -> Result<Vec<SeasonMedia>> {
+    log::info!("Getting anime of {} {}", season, year);
+    let is_adult = is_adult();
+
+    let query_string = r#"
+        query ($season: MediaSeason, $seasonYear: Int, $page: Int, $isAdult: Boolean) {
+            Page(page: $page, perPage: 50) {
+                pageInfo {
+                    hasNextPage
+                }
+                media(season: $season, seasonYear: $seasonYear, type: ANIME, isAdult: $isAdult, sort: POPULARITY_DESC) {
+                    id
+                    title {
+                        romaji
+                        english
+                        native
+                    }
+                    format
+                    genres
+                    episodes
+                    averageScore
+                    popularity
+                    startDate {
+                        year
+                        month
+                        day
+                    }
+                    coverImage {
+                        medium
+                    }
+                    mediaListEntry {
+                        status
+                        progress
+                    }
                 }
-            } else {
-                log::warn!("Failed to search. Status code: {}", res.status());
-                Err(anyhow::anyhow!("Failed to search. Status code: {}", res.status()))
             }
         }
-        Err(e) => {
-            if e.is_timeout() {
-                eprintln!(
-                    "Check your internet connection. The request to AniList API took too long. Error: {}",
-                    e
-                );
-                log::warn!("Internet connection error: {}", e);
-            } else {
-                eprintln!("Error sending request: {}", e);
-                log::error!("Request to AniList API failed: {}", e);
+    "#;
+
+    let mut media = Vec::new();
+    // * A season is a few hundred shows at most, the cap is only a safety net
+    for page in 1..=10 {
+        let variables = json!({ "season": season, "seasonYear": year, "page": page, "isAdult": is_adult });
+        let data: SeasonData = graphql::authed_query(client, query_string, variables)
+            .await
+            .with_context(|| format!("Failed to get the anime of {} {}", season, year))?;
+        media.extend(data.page.media);
+        if !data.page.page_info.has_next_page {
+            break;
+        }
+    }
+    Ok(media)
+}
+
+// =============== Information ===============
+#[derive(Deserialize)]
+struct InfoData {
+    #[serde(rename = "Media")]
+    media: InfoMedia,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InfoMedia {
+    title: Title,
+    status: Option<String>,
+    description: Option<String>,
+    #[serde(default)]
+    genres: Vec<String>,
+    episodes: Option<u32>,
+    start_date: FuzzyDate,
+    end_date: FuzzyDate,
+    format: Option<String>,
+    duration: Option<u32>,
+    season: Option<String>,
+    season_year: Option<i32>,
+    source: Option<String>,
+    average_score: Option<u32>,
+    popularity: Option<u32>,
+    studios: Option<InfoStudios>,
+    #[serde(default)]
+    tags: Vec<InfoTag>,
+    relations: Option<InfoRelations>,
+    next_airing_episode: Option<NextAiring>,
+    cover_image: Option<CoverImage>,
+    media_list_entry: Option<InfoListEntry>,
+}
+
+#[derive(Deserialize)]
+struct InfoStudios {
+    nodes: Vec<InfoStudio>,
+}
+
+#[derive(Deserialize)]
+struct InfoStudio {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InfoTag {
+    name: String,
+    rank: Option<u32>,
+    #[serde(default)]
+    is_media_spoiler: bool,
+}
+
+#[derive(Deserialize)]
+struct InfoRelations {
+    edges: Vec<InfoRelationEdge>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InfoRelationEdge {
+    relation_type: Option<String>,
+    node: InfoRelationNode,
+}
+
+#[derive(Deserialize)]
+struct InfoRelationNode {
+    title: Title,
+    format: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct InfoListEntry {
+    status: Option<String>,
+    progress: Option<u32>,
+    score: Option<f64>, // 0-100
+    #[serde(default)]
+    repeat: u32,
+    notes: Option<String>,
+}
+
+// AniList enum values (e.g. "NOT_YET_RELEASED") as words
+fn words(value: &str) -> String {
+    let lower = value.replace('_', " ").to_lowercase();
+    let mut chars = lower.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => lower,
+    }
+}
+
+// Turns the HTML AniList uses in descriptions into styled terminal text
+fn render_html(html: &str) -> String {
+    let mut out = String::new();
+    let mut text = String::new();
+    let (mut italic, mut bold) = (0, 0);
+    let flush = |text: &mut String, out: &mut String, italic: i32, bold: i32| {
+        if text.is_empty() {
+            return;
+        }
+        let mut styled = console::style(decode_entities(text));
+        if italic > 0 {
+            styled = styled.italic();
+        }
+        if bold > 0 {
+            styled = styled.bold();
+        }
+        out.push_str(&styled.to_string());
+        text.clear();
+    };
+
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            text.push_str(&rest[start..]);
+            rest = "";
+            break;
+        };
+        let tag = rest[start + 1..start + end].trim().to_lowercase();
+        rest = &rest[start + end + 1..];
+
+        let name = tag.trim_end_matches('/').split_whitespace().next().unwrap_or("");
+        flush(&mut text, &mut out, italic, bold);
+        match name {
+            "br" | "p" | "/p" => out.push('\n'),
+            "i" | "em" => italic += 1,
+            "/i" | "/em" => italic = (italic - 1).max(0),
+            "b" | "strong" => bold += 1,
+            "/b" | "/strong" => bold = (bold - 1).max(0),
+            _ => {} // Links and everything else only keep their text
+        }
+    }
+    text.push_str(rest);
+    flush(&mut text, &mut out, italic, bold);
+
+    // * AniList puts a line break before and after paragraphs, two empty lines are enough
+    let mut rendered = out.trim().to_string();
+    while rendered.contains("\n\n\n") {
+        rendered = rendered.replace("\n\n\n", "\n\n");
+    }
+    rendered
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        let after = &rest[start..];
+        let entity = after.find(';').filter(|end| *end <= 10).map(|end| &after[1..end]);
+        let character = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            "mdash" => Some('—'),
+            "ndash" => Some('–'),
+            "hellip" => Some('…'),
+            _ => {
+                let number = entity.strip_prefix('#')?;
+                let code = match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => number.parse().ok()?,
+                };
+                char::from_u32(code)
+            }
+        });
+        match (entity, character) {
+            (Some(entity), Some(character)) => {
+                decoded.push(character);
+                rest = &after[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &after[1..];
             }
-            Err(anyhow::anyhow!("Error sending request: {}", e))
         }
     }
+    decoded.push_str(rest);
+    decoded
 }
 
-// Gets the information of an anime and prints it out in a pretty way
-pub async fn information(client: &Client, anime_id: i32) -> Result<()> {
+// Gets the information of an anime and prints it out in a pretty way, or as JSON
+pub async fn information(client: &Client, anime_id: i32, as_json: bool) -> Result<()> {
     let query_string = r#"
         query ($id: Int!) {
             Media(id: $id) {
@@ -115,6 +610,7 @@ pub async fn information(client: &Client, anime_id: i32) -> Result<()> {
                 title {
                     romaji
                     english
+                    native
                 }
                 status
                 description
@@ -130,111 +626,214 @@ pub async fn information(client: &Client, anime_id: i32) -> Result<()> {
                     month
                     day
                 }
+                format
+                duration
+                season
+                seasonYear
+                source
+                averageScore
+                popularity
+                studios(isMain: true) {
+                    nodes {
+                        name
+                    }
+                }
+                tags {
+                    name
+                    rank
+                    isMediaSpoiler
+                }
+                relations {
+                    edges {
+                        relationType
+                        node {
+                            title {
+                                romaji
+                                english
+                                native
+                            }
+                            format
+                        }
+                    }
+                }
+                nextAiringEpisode {
+                    episode
+                    airingAt
+                }
+                coverImage {
+                    large
+                }
+                mediaListEntry {
+                    status
+                    progress
+                    score(format: POINT_100)
+                    repeat
+                    notes
+                }
             }
         }
     "#;
 
     let variables = json!({ "id": anime_id });
 
-    let response = client
-        .post(ANILIST_API_URL)
-        .header("Content-Type", "application/json")
-        .json(&json!({
-            "query": query_string,
-            "variables": variables
-        }))
-        .send()
-        .await;
-
-    match response {
-        Ok(res) => {
-            if res.status().is_success() {
-                let data: serde_json::Value = res.json().await.expect("Failed to parse response");
-                let media = &data["data"]["Media"];
-
-                let title = media["title"]["english"]
-                    .as_str()
-                    .unwrap_or(media["title"]["romaji"].as_str().unwrap_or("Unknown Title"));
-
-                let description = media["description"]
-                    .as_str()
-                    .unwrap_or("No description available.")
-                    .replace("<br><br>", "\n")
-                    .replace("<br>", "\n")
-                    .replace("<i>", "{")
-                    .replace("</i>", "}");
-
-                let genres = media["genres"]
-                    .as_array()
-                    .unwrap_or(&vec![])
-                    .iter()
-                    .map(|genre| genre.as_str().unwrap_or("Unknown").to_string())
-                    .collect::<Vec<String>>()
-                    .join(", ");
-
-                let episodes = media["episodes"].as_u64().unwrap_or(0) as u32;
-
-                let start_date = format!(
-                    "{}-{:02}-{:02}",
-                    media["startDate"]["year"].as_u64().unwrap_or(0),
-                    media["startDate"]["month"].as_u64().unwrap_or(0),
-                    media["startDate"]["day"].as_u64().unwrap_or(0)
-                );
-
-                let end_date = format!(
-                    "{}-{:02}-{:02}",
-                    media["endDate"]["year"].as_u64().unwrap_or(0),
-                    media["endDate"]["month"].as_u64().unwrap_or(0),
-                    media["endDate"]["day"].as_u64().unwrap_or(0)
-                );
-
-                let status = media["status"].as_str().unwrap_or("Unknown Status");
-
-                println!("{}", console::style("Anime Info").bold().underlined());
-                let design = Style::new().bold().italic().color256(247);
-
-                println!("{} {}", design.apply_to("Title:"), title);
-                println!("{} {}", design.apply_to("Current status:"), status);
-                println!(
-                    "\n{} {}\n",
-                    design.apply_to("Description:"),
-                    console::style(description).italic()
-                );
-                println!("{} {}", design.apply_to("Genres:"), genres);
-                println!("{} {}", design.apply_to("Episodes:"), episodes);
-                print!(
-                    "{} {},",
-                    design.apply_to("Start date:"),
-                    if start_date == "0-00-00" {
-                        "Unknown".to_string()
-                    } else {
-                        start_date
-                    }
-                );
-                println!(
-                    " {} {}",
-                    design.apply_to("End date:"),
-                    if end_date == "0-00-00" {
-                        "Unknown".to_string()
-                    } else {
-                        end_date
-                    }
-                );
-            } else {
-                eprintln!("Failed to fetch anime info. Status code: {}", res.status());
-                log::warn!("Failed to fetch anime info. Status code: {}", res.status());
-            }
-            Ok(())
+    // * Logged in users get their own list entry too
+    let data: serde_json::Value = graphql::viewer_query(client, query_string, variables)
+        .await
+        .map_err(|e| {
+            log::error!("Fetching info failed: {}", e);
+            e.context("Fetching info failed")
+        })?;
+    if as_json {
+        println!("{}", serde_json::to_string_pretty(&data["Media"])?);
+        return Ok(());
+    }
+    let media = serde_json::from_value::<InfoData>(data)
+        .with_context(|| "Failed to parse anime info")?
+        .media;
+
+    let title = media.title.preferred();
+
+    let description = media
+        .description
+        .as_deref()
+        .map(render_html)
+        .unwrap_or_else(|| "No description available.".to_string());
+
+    let genres = media.genres.join(", ");
+    let episodes = media
+        .episodes
+        .map(|e| e.to_string())
+        .unwrap_or_else(|| "Unknown".to_string());
+    let status = media
+        .status
+        .as_deref()
+        .map(words)
+        .unwrap_or_else(|| "Unknown Status".to_string());
+
+    if let Some(url) = media.cover_image.as_ref().and_then(|c| c.large.as_deref()) {
+        cover::print(client, url).await;
+    }
+    println!("{}", console::style("Anime Info").bold().underlined());
+    let design = Style::new().bold().italic().color256(247);
+
+    println!("{} {}", design.apply_to("Title:"), title);
+    // * The titles in the other languages, if they're different
+    for (label, other) in [
+        ("Romaji:", &media.title.romaji),
+        ("English:", &media.title.english),
+        ("Native:", &media.title.native),
+    ] {
+        if let Some(other) = other.as_ref().filter(|o| **o != title) {
+            println!("{} {}", design.apply_to(label), other);
         }
-        Err(e) => {
-            if e.is_timeout() {
-                log::warn!("Internet connection error");
-            } else {
-                log::error!("Fetching info failed: {}", e);
-            }
-            Err(anyhow::anyhow!("Fetching info failed: {}", e))
+    }
+    println!("{} {}", design.apply_to("Current status:"), status);
+    if let Some(next) = media.next_airing_episode {
+        println!(
+            "{} episode {} on {} (in {})",
+            design.apply_to("Next episode:"),
+            next.episode,
+            next.local_time(),
+            next.countdown()
+        );
+    }
+    println!("\n{}\n{}\n", design.apply_to("Description:"), description);
+
+    let format = media.format.as_deref().map(words).unwrap_or_else(|| "?".to_string());
+    let duration = media.duration.map(|d| format!(", {} min per episode", d)).unwrap_or_default();
+    println!("{} {}{}", design.apply_to("Format:"), format, duration);
+    println!("{} {}", design.apply_to("Episodes:"), episodes);
+    if let (Some(season), Some(year)) = (&media.season, media.season_year) {
+        println!("{} {} {}", design.apply_to("Season:"), words(season), year);
+    }
+    print!("{} {},", design.apply_to("Start date:"), media.start_date);
+    println!(" {} {}", design.apply_to("End date:"), media.end_date);
+    if let Some(source) = &media.source {
+        println!("{} {}", design.apply_to("Source:"), words(source));
+    }
+    let studios: Vec<String> = media
+        .studios
+        .map(|s| s.nodes.into_iter().map(|studio| studio.name).collect())
+        .unwrap_or_default();
+    if !studios.is_empty() {
+        println!("{} {}", design.apply_to("Studios:"), studios.join(", "));
+    }
+    let score = utils::score(media.average_score);
+    let popularity = media.popularity.unwrap_or(0);
+    println!(
+        "{} {} | {} {} users",
+        design.apply_to("Average score:"),
+        score,
+        design.apply_to("Popularity:"),
+        popularity
+    );
+
+    println!("\n{} {}", design.apply_to("Genres:"), genres);
+    // * Spoiler tags are left out, the rank is how well the tag fits
+    let tags: Vec<String> = media
+        .tags
+        .iter()
+        .filter(|tag| !tag.is_media_spoiler)
+        .take(10)
+        .map(|tag| match tag.rank {
+            Some(rank) => format!("{} ({}%)", tag.name, rank),
+            None => tag.name.clone(),
+        })
+        .collect();
+    if !tags.is_empty() {
+        println!("{} {}", design.apply_to("Tags:"), tags.join(", "));
+    }
+
+    let relations = media.relations.map(|r| r.edges).unwrap_or_default();
+    if !relations.is_empty() {
+        println!("\n{}", design.apply_to("Relations:"));
+        for edge in &relations {
+            println!(
+                "  {}: {} ({})",
+                edge.relation_type.as_deref().map(words).unwrap_or_else(|| "Related".to_string()),
+                edge.node.title.preferred(),
+                edge.node.format.as_deref().map(words).unwrap_or_else(|| "?".to_string())
+            );
         }
     }
+
+    if let Some(entry) = &media.media_list_entry {
+        println!("\n{}", design.apply_to("Your list entry:"));
+        let status = entry
+            .status
+            .as_deref()
+            .map(mutation::status_label)
+            .unwrap_or("Unknown");
+        println!("  {} | episode {}/{}", status, entry.progress.unwrap_or(0), episodes);
+        let raw = entry.score.unwrap_or(0.0).round() as u32;
+        let score = match score::format(client).await {
+            Ok(format) => format.display(raw),
+            Err(_) if raw > 0 => format!("{}/100", raw),
+            Err(_) => "Not yet scored".to_string(),
+        };
+        println!("  Score: {}", score);
+        if entry.repeat > 0 {
+            println!("  Rewatched {} time(s)", entry.repeat);
+        }
+        if let Some(notes) = entry.notes.as_deref().filter(|n| !n.trim().is_empty()) {
+            println!("  Notes: {}", notes);
+        }
+    }
+
+    Ok(())
+}
+
+// =============== Id converter ===============
+#[derive(Deserialize)]
+struct IdData {
+    #[serde(rename = "Media")]
+    media: IdMedia,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IdMedia {
+    id_mal: Option<i32>,
 }
 
 // Converts anilist id to mal id
@@ -248,51 +847,108 @@ pub async fn id_converter(client: &Client, id: i32) -> Result<i32> {
         }
     "#;
 
-    let variables = serde_json::json!({ "id": id });
+    let variables = json!({ "id": id });
 
-    let response = client
-        .post(ANILIST_API_URL)
-        .header("Content-Type", "application/json")
-        .json(&serde_json::json!({
-            "query": query_string,
-            "variables": variables
-        }))
-        .send()
-        .await;
+    let data: IdData = graphql::query(client, query_string, variables)
+        .await
+        .with_context(|| format!("Failed to convert id: {}", id))?;
 
-    let response = match response {
-        Ok(res) => res,
-        Err(e) => {
-            log::error!("Request error: {}", e);
-            return Err(anyhow::anyhow!("Request error: {}", e))
-        }
-    };
+    data.media.id_mal.ok_or_else(|| {
+        log::error!("No MyAnimeList id found for anilist id: {}", id);
+        anyhow::anyhow!("This anime has no MyAnimeList id")
+    })
+}
 
-    if !response.status().is_success() {
-        log::error!("Failed to convert id, status: {}", response.status());
-        return Err(anyhow::anyhow!("Failed to convert id, status: {}", response.status()))
-    }
+// =============== Titles ===============
+#[derive(Deserialize)]
+struct TitlesData {
+    #[serde(rename = "Media")]
+    media: TitlesMedia,
+}
+
+#[derive(Deserialize)]
+struct TitlesMedia {
+    title: Title,
+    #[serde(default)]
+    synonyms: Vec<String>,
+}
 
-    let data: Value = match response.json().await {
-        Ok(json) => json,
-        Err(e) => {
-            log::error!("Failed to parse response: {}", e);
-            return Err(anyhow::anyhow!("Failed to parse response: {}", e))
+// Every name the anime is known by, the preferred title first, for searching other sites
+pub async fn titles(client: &Client, id: i32) -> Result<Vec<String>> {
+    let query_string = r#"
+        query ($id: Int) {
+            Media(id: $id) {
+                title {
+                    romaji
+                    english
+                    native
+                }
+                synonyms
+            }
         }
-    };
+    "#;
 
-    let mal_id = data["data"]["Media"]["idMal"].as_i64().map(|id| id as i32);
+    let variables = json!({ "id": id });
+
+    let data: TitlesData = graphql::query(client, query_string, variables)
+        .await
+        .with_context(|| format!("Failed to get the titles of {}", id))?;
 
-    Ok(mal_id.unwrap())
+    let mut titles = data.media.title.all();
+    for synonym in data.media.synonyms {
+        if !synonym.trim().is_empty() && !titles.contains(&synonym) {
+            titles.push(synonym);
+        }
+    }
+    Ok(titles)
 }
 
+// =============== Anime data ===============
 // gets anime name and episode count by id
 #[derive(Clone)]
 pub struct AnimeData {
     pub title: String,
     pub episodes: u32,
-    pub id: i32,
     pub large_pic: Option<String>,
+    pub next_airing: Option<NextAiring>, // None once the show finished airing
+}
+
+impl AnimeData {
+    // Episodes that are already out, AniList has no episode count for most airing shows
+    pub fn aired_episodes(&self) -> u32 {
+        match self.next_airing {
+            Some(next) => next.episode.saturating_sub(1),
+            None => self.episodes,
+        }
+    }
+
+    // Episode count, or the aired episodes if the count isn't known yet
+    pub fn known_episodes(&self) -> u32 {
+        if self.episodes > 0 {
+            self.episodes
+        } else {
+            self.aired_episodes()
+        }
+    }
+
+    pub fn has_aired(&self, episode: u32) -> bool {
+        self.next_airing.is_none_or(|next| episode < next.episode)
+    }
+}
+
+#[derive(Deserialize)]
+struct MediaData {
+    #[serde(rename = "Media")]
+    media: Media,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Media {
+    title: Title,
+    episodes: Option<u32>,
+    cover_image: Option<CoverImage>,
+    next_airing_episode: Option<NextAiring>,
 }
 
 pub async fn data_by_id(client: &Client, id: i32) -> Result<AnimeData> {
@@ -303,11 +959,15 @@ pub async fn data_by_id(client: &Client, id: i32) -> Result<AnimeData> {
                 title {
                     romaji
                     english
+                    native
                 }
                 episodes
                 coverImage {
                     large
-                    medium
+                }
+                nextAiringEpisode {
+                    episode
+                    airingAt
                 }
             }
         }
@@ -315,144 +975,358 @@ pub async fn data_by_id(client: &Client, id: i32) -> Result<AnimeData> {
 
     let variables = json!({ "id": id });
 
-    let response = client
-        .post(ANILIST_API_URL)
-        .header("Content-Type", "application/json")
-        .json(&json!({
-            "query": query_string,
-            "variables": variables
-        }))
-        .send()
-        .await;
-
-    match response {
-        Ok(res) => match res.json::<serde_json::Value>().await {
-            Ok(json) => {
-                let titles = &json["data"]["Media"]["title"];
-                let covers = &json["data"]["Media"]["coverImage"];
-                let title = titles["english"].as_str().unwrap_or_else(|| titles["romaji"].as_str().unwrap_or(""));
-                let episodes = json["data"]["Media"]["episodes"].as_u64().unwrap_or(0) as u32;
-                let large = covers["large"].as_str().unwrap_or("");
-
-                if title.is_empty() {
-                    return Err(anyhow::anyhow!("Failed to parse title data"));
-                }
-                else {
-                    return Ok(
-                        AnimeData{
-                            id: id,
-                            episodes: episodes,
-                            title: title.to_string(),
-                            large_pic: Some(large.to_string()),
-                        }
-                    );
-                }
+    let data: MediaData = graphql::query(client, query_string, variables)
+        .await
+        .map_err(|e| {
+            log::error!("Error searching for name: {}", e);
+            e.context("Error searching for name")
+        })?;
+    let media = data.media;
 
-            }
-            Err(e) => {
-                log::error!("JSON parsing error: {}", e);
-                Err(anyhow::anyhow!("Failed to parse response JSON: {}", e))
-            }
-        },
-        Err(e) => {
-            if e.is_timeout() {
-                log::warn!("Internet connection error");
-                Err(anyhow::anyhow!("Internet connection error"))
-            } else {
-                log::error!("Error searching for name: {}", e);
-                Err(anyhow::anyhow!("Error searching for name: {}", e))
-            }
+    Ok(AnimeData {
+        episodes: media.episodes.unwrap_or(0),
+        title: media.title.preferred(),
+        large_pic: media.cover_image.and_then(|c| c.large),
+        next_airing: media.next_airing_episode,
+    })
+}
+
+// =============== Franchise ===============
+// Relations that belong to the story, spin-offs and adaptations are left out
+const STORY_RELATIONS: [&str; 4] = ["PREQUEL", "SEQUEL", "SIDE_STORY", "PARENT"];
+// * Long running franchises can have a lot of specials, this keeps the walk from running forever
+const MAX_FRANCHISE: usize = 60;
+
+#[derive(Deserialize)]
+struct RelationData {
+    #[serde(rename = "Media")]
+    media: RelationMedia,
+}
+
+#[derive(Deserialize)]
+struct RelationMedia {
+    relations: Relations,
+    #[serde(flatten)]
+    details: RelationNode,
+}
+
+#[derive(Deserialize)]
+struct Relations {
+    edges: Vec<RelationEdge>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RelationEdge {
+    relation_type: Option<String>,
+    node: RelationNode,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RelationNode {
+    id: i32,
+    title: Title,
+    format: Option<String>,
+    episodes: Option<u32>,
+    #[serde(default)]
+    start_date: FuzzyDate,
+    cover_image: Option<CoverImage>,
+    media_list_entry: Option<ListStatus>,
+    #[serde(rename = "type")]
+    media_type: Option<String>,
+}
+
+// An anime of the franchise
+#[derive(Clone)]
+pub struct FranchiseEntry {
+    pub id: i32,
+    pub title: String,
+    pub format: Option<String>,
+    pub episodes: Option<u32>,
+    pub start_date: FuzzyDate,
+    pub relation: Option<String>, // How it was reached, None for the anime the walk started from
+    pub list_entry: Option<ListStatus>,
+    pub cover: Option<String>,
+}
+
+impl FranchiseEntry {
+    fn new(node: RelationNode, relation: Option<String>) -> Self {
+        Self {
+            id: node.id,
+            title: node.title.preferred(),
+            format: node.format,
+            episodes: node.episodes,
+            start_date: node.start_date,
+            relation,
+            list_entry: node.media_list_entry,
+            cover: node.cover_image.and_then(|c| c.medium),
         }
     }
+
+    pub fn completed(&self) -> bool {
+        self.list_entry
+            .as_ref()
+            .and_then(|e| e.status.as_deref())
+            .is_some_and(|status| status == "COMPLETED")
+    }
 }
 
-// gets sequel data by id
-pub async fn sequel_data(client: &Client, id: i32) -> Result<AnimeData> {
+async fn relations(client: &Client, id: i32) -> Result<RelationMedia> {
     let query_string = r#"query ($id: Int) {
         Media(id: $id, type: ANIME) {
-            title {
-                romaji
-                english
-            }
+            ...details
             relations {
                 edges {
                     relationType
-                        node {
+                    node {
+                        ...details
+                    }
+                }
+            }
+        }
+    }
+
+    fragment details on Media {
+        id
+        title {
+            romaji
+            english
+            native
+        }
+        format
+        episodes
+        startDate {
+            year
+            month
+            day
+        }
+        coverImage {
+            medium
+        }
+        mediaListEntry {
+            status
+            progress
+        }
+        type
+    }"#;
+    let variables = json!({"id": id});
+
+    let data: RelationData = graphql::authed_query(client, query_string, variables)
+        .await
+        .map_err(|e| {
+            log::error!("Error getting relations for {}: {}", id, e);
+            e.context("Error getting relations")
+        })?;
+    Ok(data.media)
+}
+
+// What can be watched after the anime, the sequel first and then the side stories
+// Anything already completed is left out, unless the user is rewatching the franchise
+pub async fn next_parts(client: &Client, id: i32, rewatching: bool) -> Result<Vec<FranchiseEntry>> {
+    let media = relations(client, id).await?;
+    let mut parts: Vec<FranchiseEntry> = media
+        .relations
+        .edges
+        .into_iter()
+        .filter(|edge| edge.node.media_type.as_deref() == Some("ANIME"))
+        .filter(|edge| matches!(edge.relation_type.as_deref(), Some("SEQUEL" | "SIDE_STORY")))
+        .map(|edge| FranchiseEntry::new(edge.node, edge.relation_type))
+        .filter(|entry| rewatching || !entry.completed())
+        .collect();
+    parts.sort_by_key(|entry| entry.relation.as_deref() != Some("SEQUEL"));
+    Ok(parts)
+}
+
+// Walks every story relation of the anime, returns the whole franchise in release order
+pub async fn franchise(client: &Client, id: i32) -> Result<Vec<FranchiseEntry>> {
+    let start = relations(client, id).await?;
+    let mut entries: Vec<FranchiseEntry> = Vec::new();
+    let mut layer: Vec<(RelationMedia, Option<String>)> = vec![(start, None)];
+
+    while !layer.is_empty() && entries.len() < MAX_FRANCHISE {
+        let mut next: Vec<(i32, Option<String>)> = Vec::new();
+        for (media, relation) in layer {
+            for edge in &media.relations.edges {
+                let known = entries.iter().any(|e| e.id == edge.node.id)
+                    || next.iter().any(|(id, _)| *id == edge.node.id)
+                    || edge.node.id == media.details.id;
+                if !known
+                    && edge.node.media_type.as_deref() == Some("ANIME")
+                    && edge
+                        .relation_type
+                        .as_deref()
+                        .is_some_and(|r| STORY_RELATIONS.contains(&r))
+                {
+                    next.push((edge.node.id, edge.relation_type.clone()));
+                }
+            }
+            entries.push(FranchiseEntry::new(media.details, relation));
+        }
+        next.retain(|(id, _)| !entries.iter().any(|e| e.id == *id));
+        next.truncate(MAX_FRANCHISE.saturating_sub(entries.len()));
+
+        // * One layer at a time, everything in it is asked for at once
+        let fetched = join_all(next.iter().map(|(id, _)| relations(client, *id))).await;
+        layer = Vec::new();
+        for ((id, relation), media) in next.into_iter().zip(fetched) {
+            match media {
+                Ok(media) => layer.push((media, relation)),
+                Err(e) => log::warn!("Skipping {} in the franchise: {:#}", id, e),
+            }
+        }
+    }
+
+    // * Release order is the safest watch order, unknown dates go last
+    entries.sort_by_key(|e| {
+        let date = &e.start_date;
+        (date.year.is_none(), date.year, date.month, date.day, e.id)
+    });
+    Ok(entries)
+}
+
+// =============== Recommendations ===============
+#[derive(Deserialize)]
+struct RecommendationData {
+    #[serde(rename = "Media")]
+    media: RecommendationMedia,
+}
+
+#[derive(Deserialize)]
+struct RecommendationMedia {
+    recommendations: RecommendationConnection,
+}
+
+#[derive(Deserialize)]
+struct RecommendationConnection {
+    nodes: Vec<RecommendationNode>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RecommendationNode {
+    rating: Option<i32>,
+    media_recommendation: Option<RecommendedMedia>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RecommendedMedia {
+    id: i32,
+    title: Title,
+    format: Option<String>,
+    average_score: Option<u32>,
+    cover_image: Option<CoverImage>,
+    media_list_entry: Option<ListStatus>,
+}
+
+#[derive(Clone)]
+pub struct Recommendation {
+    pub id: i32,
+    pub title: String,
+    pub format: Option<String>,
+    pub average_score: Option<u32>,
+    pub rating: i32, // How many users agreed with the recommendation
+    pub cover: Option<String>,
+}
+
+// Recommendations for an anime that aren't on the user's list yet, best rated first
+pub async fn recommendations(client: &Client, id: i32) -> Result<Vec<Recommendation>> {
+    let query_string = r#"
+        query ($id: Int) {
+            Media(id: $id, type: ANIME) {
+                recommendations(sort: RATING_DESC, perPage: 15) {
+                    nodes {
+                        rating
+                        mediaRecommendation {
                             id
                             title {
                                 romaji
                                 english
+                                native
                             }
-                            episodes
+                            format
+                            averageScore
                             coverImage {
-                                large
                                 medium
                             }
-                        type
+                            mediaListEntry {
+                                status
+                                progress
+                            }
+                        }
                     }
                 }
             }
         }
-    }"#;
-    let variables = json!({"id": id});
-
-    let response = client
-        .post(ANILIST_API_URL)
-        .header("Content-Type", "application/json")
-        .json(&json!({
-            "query": query_string,
-            "variables": variables,
-        }))
-        .send()
-        .await;
-
-    match response {
-        Ok(res) => match res.json::<serde_json::Value>().await {
-            Ok(json) => {
-                let edges_array = json["data"]["Media"]["relations"]["edges"]
-                    .as_array();
-                let edges = match edges_array {
-                    Some(arr) => arr,
-                    None => {
-                        return Err(anyhow::anyhow!("Failed to parse edges array"));
-                    }
-                };
+    "#;
+    let variables = json!({ "id": id });
 
-                for edge in edges {
-                    let relation_type = edge["relationType"].as_str().unwrap_or("");
-                    if relation_type == "SEQUEL" {
-                        let node = &edge["node"]["title"];
-                        let title = node["english"].as_str().unwrap_or_else(|| node["romaji"].as_str().unwrap_or("<Unknown Title>"));
-                        let sequel_id = edge["node"]["id"].as_i64().unwrap() as i32;
-                        let episodes = edge["node"]["episodes"].as_u64().unwrap_or(0) as u32;
-                        if !title.is_empty() && sequel_id != 0 {
-                            return Ok(AnimeData{
-                                title: title.to_string(),
-                                episodes: episodes,
-                                id: sequel_id,
-                                large_pic: None,
-                            });
-                        } else {
-                            return Err(anyhow::anyhow!("Failed to parse sequel data"));
-                        }
+    let data: RecommendationData = graphql::authed_query(client, query_string, variables)
+        .await
+        .with_context(|| format!("Failed to get recommendations for {}", id))?;
 
-                    }
-                }
-                Err(anyhow::anyhow!("No sequel found"))
+    Ok(data
+        .media
+        .recommendations
+        .nodes
+        .into_iter()
+        .filter_map(|node| {
+            let media = node.media_recommendation?;
+            if media.media_list_entry.is_some() {
+                return None;
             }
-            Err(e) => {
-                log::error!("JSON parsing error: {}", e);
-                Err(anyhow::anyhow!("Failed to parse response JSON: {}", e))
-            }
-        },
-        Err(e) => {
-            if e.is_timeout() {
-                log::warn!("Internet connection error");
-                Err(anyhow::anyhow!("Internet connection error"))
-            } else {
-                log::error!("Error getting relations for name: {}", e);
-                Err(anyhow::anyhow!("Error getting relations for name: {}", e))
-            }
-        }
+            Some(Recommendation {
+                id: media.id,
+                title: media.title.preferred(),
+                format: media.format,
+                average_score: media.average_score,
+                rating: node.rating.unwrap_or(0),
+                cover: media.cover_image.and_then(|c| c.medium),
+            })
+        })
+        .filter(|r| r.rating > 0)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_named_and_numeric_entities() {
+        assert_eq!(decode_entities("Tom &amp; Jerry"), "Tom & Jerry");
+        assert_eq!(decode_entities("&lt;b&gt; &quot;hi&quot; &apos;"), "<b> \"hi\" '");
+        assert_eq!(decode_entities("wait&hellip; &#8212; &#x2014;"), "wait… — —");
+    }
+
+    #[test]
+    fn keeps_unknown_or_broken_entities() {
+        assert_eq!(decode_entities("&unknown; & more"), "&unknown; & more");
+        assert_eq!(decode_entities("a &amp"), "a &amp");
+        assert_eq!(decode_entities("&#xZZ;"), "&#xZZ;");
+    }
+
+    #[test]
+    fn renders_line_breaks_and_drops_tags() {
+        console::set_colors_enabled(false);
+        assert_eq!(render_html("One<br>Two<br/>Three"), "One\nTwo\nThree");
+        assert_eq!(
+            render_html("<i>Source: <a href=\"x\">Site</a></i>"),
+            "Source: Site"
+        );
+        assert_eq!(render_html("A &amp; B <b>bold</b>"), "A & B bold");
+    }
+
+    #[test]
+    fn collapses_empty_lines() {
+        console::set_colors_enabled(false);
+        assert_eq!(render_html("<br><br>First<br><br><br><br>Second<br>"), "First\n\nSecond");
+    }
+
+    #[test]
+    fn keeps_text_after_an_unclosed_tag() {
+        console::set_colors_enabled(false);
+        assert_eq!(render_html("Text <unclosed"), "Text <unclosed");
     }
 }
@@ -0,0 +1,409 @@
+// Shared AniList GraphQL client, every query and mutation goes through here
+
+// =============== Imports ================
+use crate::api::anilist::auth;
+use crate::config::{self, TitleLanguage};
+use crate::http::{self, Service};
+
+use anyhow::{Context, Result};
+use chrono::{Datelike, Local, TimeZone};
+use reqwest::{Client, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Constant variables
+const ANILIST_API_URL: &str = "https://graphql.anilist.co";
+
+#[derive(Deserialize)]
+struct GraphQLResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphQLError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GraphQLError {
+    message: String,
+    status: Option<u16>,
+    // Only filled for invalid mutation arguments, field name -> problems
+    validation: Option<HashMap<String, Vec<String>>>,
+}
+
+impl fmt::Display for GraphQLError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(validation) = &self.validation {
+            for (field, problems) in validation {
+                write!(f, " ({}: {})", field, problems.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Errors AniList sent back, kept as a type so callers can tell them apart from network errors
+#[derive(Debug)]
+pub struct AniListError {
+    pub status: Option<u16>,
+    pub message: String,
+}
+
+impl AniListError {
+    pub fn is_invalid_token(&self) -> bool {
+        self.message.contains("Invalid token") || self.status == Some(401)
+    }
+}
+
+impl fmt::Display for AniListError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_invalid_token() {
+            write!(
+                f,
+                "AniList rejected the token ({}), please log in again with --change-token",
+                self.message
+            )
+        } else {
+            write!(f, "AniList returned an error: {}", self.message)
+        }
+    }
+}
+
+impl std::error::Error for AniListError {}
+
+// Sends a query without authentication
+pub async fn query<T: DeserializeOwned>(client: &Client, query: &str, variables: Value) -> Result<T> {
+    send(client, query, variables, None).await
+}
+
+// Sends a query (or mutation) as the logged in user
+pub async fn authed_query<T: DeserializeOwned>(
+    client: &Client,
+    query: &str,
+    variables: Value,
+) -> Result<T> {
+    let token = auth::token().await?;
+    send(client, query, variables, Some(&token)).await
+}
+
+// Sends a query as the logged in user if there is one, without authentication otherwise
+// For queries that also work without the user's data (e.g. mediaListEntry is null then)
+pub async fn viewer_query<T: DeserializeOwned>(
+    client: &Client,
+    query: &str,
+    variables: Value,
+) -> Result<T> {
+    let token = auth::existing_token();
+    send(client, query, variables, token.as_deref()).await
+}
+
+async fn send<T: DeserializeOwned>(
+    client: &Client,
+    query: &str,
+    variables: Value,
+    token: Option<&str>,
+) -> Result<T> {
+    let mut request = client
+        .post(ANILIST_API_URL)
+        .header("Content-Type", "application/json")
+        .header("Accept", "application/json")
+        .json(&json!({
+            "query": query,
+            "variables": variables
+        }));
+    if let Some(token) = token {
+        request = request.header("Authorization", format!("Bearer {}", token.trim()));
+    }
+
+    let response = http::send(Service::AniList, request)
+        .await
+        .with_context(|| "Failed to send request to AniList API")?;
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .with_context(|| "Failed to read response from AniList API")?;
+
+    // AniList sends errors as JSON too, even with error status codes
+    let parsed: GraphQLResponse<T> = match serde_json::from_str(&body) {
+        Ok(parsed) => parsed,
+        Err(e) if status.is_success() => {
+            log::error!("Unexpected AniList response: {}", body);
+            return Err(e).with_context(|| "Failed to parse AniList response");
+        }
+        Err(_) => return Err(status_error(status)),
+    };
+
+    if let Some(error) = parsed.errors.first() {
+        let message = parsed
+            .errors
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join("; ");
+        log::error!("AniList errors: {:?}", parsed.errors);
+        return Err(AniListError {
+            status: error.status.or(Some(status.as_u16())),
+            message,
+        }
+        .into());
+    }
+
+    match parsed.data {
+        Some(data) => Ok(data),
+        None => Err(status_error(status)),
+    }
+}
+
+fn status_error(status: StatusCode) -> anyhow::Error {
+    log::error!("AniList request failed with status: {}", status);
+    AniListError {
+        status: Some(status.as_u16()),
+        message: format!("request failed with status {}", status),
+    }
+    .into()
+}
+
+// =============== Shared types ===============
+
+// The title language used everywhere, "anilist" from the config is resolved to the user's setting
+static TITLE_LANGUAGE: OnceLock<TitleLanguage> = OnceLock::new();
+
+#[derive(Deserialize)]
+struct TitleLanguageData {
+    #[serde(rename = "Viewer")]
+    viewer: TitleLanguageViewer,
+}
+
+#[derive(Deserialize)]
+struct TitleLanguageViewer {
+    options: Option<TitleLanguageOptions>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TitleLanguageOptions {
+    title_language: Option<String>,
+}
+
+// Reads the title language from the config, asking AniList for it if the user wants theirs
+// Without a login (or a connection) AniList's default, romaji, is used
+pub async fn init_title_language(client: &Client) {
+    let language = match config::load_config().title_language {
+        TitleLanguage::Anilist if auth::existing_token().is_some() => {
+            let query = r#"
+                query {
+                    Viewer {
+                        options {
+                            titleLanguage
+                        }
+                    }
+                }
+            "#;
+            match viewer_query::<TitleLanguageData>(client, query, json!({})).await {
+                // * The stylised variants only change capitalization, so they count as the plain ones
+                Ok(data) => match data.viewer.options.and_then(|o| o.title_language).as_deref() {
+                    Some(l) if l.starts_with("ENGLISH") => TitleLanguage::English,
+                    Some(l) if l.starts_with("NATIVE") => TitleLanguage::Native,
+                    _ => TitleLanguage::Romaji,
+                },
+                Err(e) => {
+                    log::warn!("Couldn't get the AniList title language: {:#}", e);
+                    TitleLanguage::Romaji
+                }
+            }
+        }
+        TitleLanguage::Anilist => TitleLanguage::Romaji,
+        language => language,
+    };
+    log::info!("Title language: {:?}", language);
+    let _ = TITLE_LANGUAGE.set(language);
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Title {
+    pub romaji: Option<String>,
+    pub english: Option<String>,
+    pub native: Option<String>,
+}
+
+impl Title {
+    // The language the titles are shown in
+    pub fn language() -> TitleLanguage {
+        *TITLE_LANGUAGE.get_or_init(|| config::load_config().title_language)
+    }
+
+    // Every title it has, the one in the chosen language first
+    pub fn all(&self) -> Vec<String> {
+        let order = match Self::language() {
+            TitleLanguage::English => [&self.english, &self.romaji, &self.native],
+            TitleLanguage::Native => [&self.native, &self.romaji, &self.english],
+            TitleLanguage::Romaji | TitleLanguage::Anilist => {
+                [&self.romaji, &self.english, &self.native]
+            }
+        };
+        let mut titles: Vec<String> = Vec::new();
+        for title in order.into_iter().flatten() {
+            if !titles.contains(title) {
+                titles.push(title.clone());
+            }
+        }
+        titles
+    }
+
+    // Title in the chosen language, the other ones are used if it doesn't have one
+    pub fn preferred(&self) -> String {
+        self.all()
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| "Unknown Title".to_string())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct FuzzyDate {
+    pub year: Option<u32>,
+    pub month: Option<u32>,
+    pub day: Option<u32>,
+}
+
+impl FuzzyDate {
+    pub fn is_set(&self) -> bool {
+        self.year.is_some()
+    }
+
+    // Today's date where the user is
+    pub fn today() -> Self {
+        let today = Local::now().date_naive();
+        Self {
+            year: Some(today.year() as u32),
+            month: Some(today.month()),
+            day: Some(today.day()),
+        }
+    }
+
+    // Reads YYYY, YYYY-MM or YYYY-MM-DD
+    pub fn parse(input: &str) -> Option<Self> {
+        let mut parts = input.trim().split('-').map(|p| p.parse::<u32>());
+        let year = parts.next()?.ok()?;
+        let month = parts.next().transpose().ok()?;
+        let day = parts.next().transpose().ok()?;
+        let valid = (1900..=2200).contains(&year)
+            && month.is_none_or(|m| (1..=12).contains(&m))
+            && day.is_none_or(|d| (1..=31).contains(&d))
+            && (day.is_none() || month.is_some())
+            && parts.next().is_none();
+        valid.then_some(Self {
+            year: Some(year),
+            month,
+            day,
+        })
+    }
+}
+
+impl fmt::Display for FuzzyDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.year {
+            Some(year) => write!(
+                f,
+                "{}-{:02}-{:02}",
+                year,
+                self.month.unwrap_or(0),
+                self.day.unwrap_or(0)
+            ),
+            None => write!(f, "Unknown"),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct CoverImage {
+    pub large: Option<String>,
+    pub medium: Option<String>, // Small enough for lists
+}
+
+// Next episode of a show that is still airing
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct NextAiring {
+    pub episode: u32,
+    pub airing_at: i64, // Unix timestamp
+}
+
+impl NextAiring {
+    // Time left until the episode airs, e.g. "3 days" or "5 hours"
+    pub fn countdown(&self) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        let seconds = (self.airing_at - now).max(0);
+        let (amount, unit) = match seconds {
+            0..=3599 => (seconds / 60, "minute"),
+            3600..=86399 => (seconds / 3600, "hour"),
+            _ => (seconds / 86400, "day"),
+        };
+        if amount == 1 {
+            format!("1 {}", unit)
+        } else {
+            format!("{} {}s", amount, unit)
+        }
+    }
+
+    // When the episode airs in the user's time zone, e.g. "Sat 18:30"
+    pub fn local_time(&self) -> String {
+        match Local.timestamp_opt(self.airing_at, 0).single() {
+            Some(time) => time.format("%a %H:%M").to_string(),
+            None => "Unknown".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: u32, month: Option<u32>, day: Option<u32>) -> FuzzyDate {
+        FuzzyDate {
+            year: Some(year),
+            month,
+            day,
+        }
+    }
+
+    #[test]
+    fn parses_partial_dates() {
+        assert_eq!(FuzzyDate::parse("2024"), Some(date(2024, None, None)));
+        assert_eq!(FuzzyDate::parse("2024-03"), Some(date(2024, Some(3), None)));
+        assert_eq!(FuzzyDate::parse(" 2024-03-09 "), Some(date(2024, Some(3), Some(9))));
+    }
+
+    #[test]
+    fn rejects_invalid_dates() {
+        let invalid = [
+            "", "today", "24-03-09", "2024-13", "2024-03-32", "2024-00-10", "2024-03-09-01", "2024/03/09",
+        ];
+        for input in invalid {
+            assert_eq!(FuzzyDate::parse(input), None, "{}", input);
+        }
+    }
+
+    #[test]
+    fn today_matches_the_calendar() {
+        let now = Local::now();
+        let today = FuzzyDate::today();
+        assert_eq!(today, date(now.year() as u32, Some(now.month()), Some(now.day())));
+        assert!(today.is_set());
+        assert!(!FuzzyDate::default().is_set());
+    }
+
+    #[test]
+    fn shows_missing_parts_as_zero() {
+        assert_eq!(date(2024, Some(3), None).to_string(), "2024-03-00");
+        assert_eq!(FuzzyDate::default().to_string(), "Unknown");
+    }
+}
@@ -0,0 +1,487 @@
+// Everything about a list entry that isn't progress, status or score
+// Notes, dates, rewatch count, private, custom lists and advanced scores
+
+// =============== Imports ================
+use crate::api::anilist::fetch::ListStatus;
+use crate::api::anilist::graphql::{self, FuzzyDate};
+use crate::api::anilist::mutation::{self, COMPLETED, CURRENT, REPEATING};
+use crate::api::anilist::outbox::{self, Change};
+use crate::api::anilist::score;
+use crate::api::anilist::user_fetch::AnimeData;
+use crate::config::Config;
+use crate::local_save;
+use crate::theme;
+use crate::utils;
+
+use anyhow::{Context, Result};
+use console::style;
+use dialoguer::{Input, MultiSelect, Select};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use std::collections::HashMap;
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ListEntry {
+    pub status: Option<String>,
+    #[serde(default)]
+    pub repeat: u32,
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub private: bool,
+    #[serde(default)]
+    pub hidden_from_status_lists: bool,
+    #[serde(default)]
+    pub started_at: FuzzyDate,
+    #[serde(default)]
+    pub completed_at: FuzzyDate,
+    // List name -> is the anime in it
+    pub custom_lists: Option<HashMap<String, bool>>,
+    // Category -> score
+    pub advanced_scores: Option<HashMap<String, f64>>,
+}
+
+// What the user set up for their anime list
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ListOptions {
+    #[serde(default)]
+    pub custom_lists: Vec<String>,
+    #[serde(default)]
+    pub advanced_scoring: Vec<String>,
+    #[serde(default)]
+    pub advanced_scoring_enabled: bool,
+}
+
+#[derive(Deserialize)]
+struct EntryData {
+    #[serde(rename = "Media")]
+    media: EntryMedia,
+    #[serde(rename = "Viewer")]
+    viewer: Viewer,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EntryMedia {
+    media_list_entry: Option<ListEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Viewer {
+    media_list_options: MediaListOptions,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MediaListOptions {
+    anime_list: Option<ListOptions>,
+}
+
+// Gets the user's entry of the anime (None if it isn't on their list) and their list options
+pub async fn get(client: &Client, media_id: i32) -> Result<(Option<ListEntry>, ListOptions)> {
+    let query_string = r#"
+        query ($mediaId: Int) {
+            Media(id: $mediaId) {
+                mediaListEntry {
+                    status
+                    repeat
+                    notes
+                    private
+                    hiddenFromStatusLists
+                    startedAt {
+                        year
+                        month
+                        day
+                    }
+                    completedAt {
+                        year
+                        month
+                        day
+                    }
+                    customLists(asArray: false)
+                    advancedScores
+                }
+            }
+            Viewer {
+                mediaListOptions {
+                    animeList {
+                        customLists
+                        advancedScoring
+                        advancedScoringEnabled
+                    }
+                }
+            }
+        }
+    "#;
+
+    let data: EntryData = graphql::authed_query(client, query_string, json!({ "mediaId": media_id }))
+        .await
+        .with_context(|| format!("Failed to get list entry of {}", media_id))?;
+    Ok((
+        data.media.media_list_entry,
+        data.viewer.media_list_options.anime_list.unwrap_or_default(),
+    ))
+}
+
+// Saves only the given fields, the keys are SaveMediaListEntry arguments
+pub async fn save(client: &Client, media_id: i32, fields: Map<String, Value>) -> Result<()> {
+    let query_string = r#"
+        mutation (
+            $mediaId: Int,
+            $notes: String,
+            $repeat: Int,
+            $private: Boolean,
+            $hiddenFromStatusLists: Boolean,
+            $startedAt: FuzzyDateInput,
+            $completedAt: FuzzyDateInput,
+            $customLists: [String],
+            $advancedScores: [Float]
+        ) {
+            SaveMediaListEntry(
+                mediaId: $mediaId,
+                notes: $notes,
+                repeat: $repeat,
+                private: $private,
+                hiddenFromStatusLists: $hiddenFromStatusLists,
+                startedAt: $startedAt,
+                completedAt: $completedAt,
+                customLists: $customLists,
+                advancedScores: $advancedScores
+            ) {
+                id
+            }
+        }
+    "#;
+
+    // * Variables that aren't sent are left alone by AniList
+    let mut variables = fields;
+    variables.insert("mediaId".to_string(), json!(media_id));
+
+    let _: Value = graphql::authed_query(client, query_string, Value::Object(variables))
+        .await
+        .with_context(|| "Failed to save list entry")?;
+    log::info!("Saved list entry of {}", media_id);
+    Ok(())
+}
+
+// Runs after progress was synced, moves the status along and fills in the dates
+// Every status change can be turned off in the config
+pub async fn after_sync(client: &Client, media_id: i32, episode: u32, max_ep: u32, config: &Config) {
+    let first = episode == 1;
+    let last = max_ep > 0 && episode >= max_ep;
+
+    let entry = match get(client, media_id).await {
+        Ok((Some(entry), _)) => entry,
+        Ok((None, _)) => return,
+        Err(e) => {
+            log::warn!("Couldn't check list entry of {}: {:#}", media_id, e);
+            return;
+        }
+    };
+
+    if let Err(e) = update_status(client, media_id, &entry, last, config).await {
+        log::warn!("Failed to update status of {}: {:#}", media_id, e);
+    }
+
+    // * Dates that are already set are left alone, rewatches don't touch them at all
+    if entry.status.as_deref() == Some("REPEATING") || (!first && !last) {
+        return;
+    }
+
+    let today = FuzzyDate::today();
+    let mut changes = Vec::new();
+    if first && !entry.started_at.is_set() {
+        changes.push(Change::StartedAt { date: today.clone() });
+    }
+    if last && !entry.completed_at.is_set() {
+        // * Shows finished in one go also get a start date
+        if !entry.started_at.is_set() && !first {
+            changes.push(Change::StartedAt { date: today.clone() });
+        }
+        changes.push(Change::CompletedAt { date: today });
+    }
+
+    for change in changes {
+        if let Err(e) = outbox::submit(client, media_id, change).await {
+            log::warn!("Failed to set list date of {}: {:#}", media_id, e);
+        }
+    }
+}
+
+async fn update_status(
+    client: &Client,
+    media_id: i32,
+    entry: &ListEntry,
+    last: bool,
+    config: &Config,
+) -> Result<()> {
+    match entry.status.as_deref() {
+        Some("REPEATING") if last && config.auto_status_rewatch_completed => {
+            log::info!("Rewatch of {} finished", media_id);
+            mutation::update_status(client, media_id, COMPLETED).await?;
+            outbox::submit(client, media_id, Change::Repeat { count: entry.repeat + 1 }).await?;
+        }
+        Some("CURRENT") if last && config.auto_status_completed => {
+            mutation::update_status(client, media_id, COMPLETED).await?;
+        }
+        Some("PLANNING") | Some("PAUSED") => {
+            if last && config.auto_status_completed {
+                mutation::update_status(client, media_id, COMPLETED).await?;
+            } else if config.auto_status_watching {
+                mutation::update_status(client, media_id, CURRENT).await?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// =============== Editor ===============
+// Lets the user change every field, nothing is sent until they save
+pub async fn interactive_edit(client: &Client, media_id: i32) -> Result<()> {
+    let (entry, options) = get(client, media_id).await?;
+    let Some(mut entry) = entry else {
+        println!("This anime is not on your list yet.");
+        return Ok(());
+    };
+
+    let theme = theme::CustomTheme {};
+    let mut changes: Map<String, Value> = Map::new();
+
+    loop {
+        let custom_lists: Vec<String> = entry
+            .custom_lists
+            .iter()
+            .flatten()
+            .filter(|(_, on)| **on)
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        let mut items = vec![
+            format!("Notes: {}", entry.notes.as_deref().filter(|n| !n.is_empty()).unwrap_or("-")),
+            format!("Started: {}", entry.started_at),
+            format!("Completed: {}", entry.completed_at),
+            format!("Rewatches: {}", entry.repeat),
+            format!("Private: {}", entry.private),
+            format!("Hidden from status lists: {}", entry.hidden_from_status_lists),
+            format!("Custom lists: {}", if custom_lists.is_empty() { "-".to_string() } else { custom_lists.join(", ") }),
+        ];
+        if options.advanced_scoring_enabled {
+            items.push("Advanced scores".to_string());
+        }
+        items.push(style("Save").green().to_string());
+        items.push(style("Discard").red().to_string());
+        let save_index = items.len() - 2;
+
+        let selection = Select::with_theme(&theme)
+            .with_prompt(format!("Edit list entry ({} unsaved)", changes.len()))
+            .items(&items)
+            .default(0)
+            .interact_opt()?;
+        utils::clear();
+
+        match selection {
+            Some(0) => {
+                let notes: String = Input::with_theme(&theme)
+                    .with_prompt("Notes (leave empty to remove)")
+                    .with_initial_text(entry.notes.clone().unwrap_or_default())
+                    .allow_empty(true)
+                    .interact_text()?;
+                changes.insert("notes".to_string(), json!(notes));
+                entry.notes = Some(notes);
+            }
+            Some(1) => {
+                entry.started_at = ask_date(&theme, "Started on")?;
+                changes.insert("startedAt".to_string(), json!(entry.started_at));
+            }
+            Some(2) => {
+                entry.completed_at = ask_date(&theme, "Completed on")?;
+                changes.insert("completedAt".to_string(), json!(entry.completed_at));
+            }
+            Some(3) => {
+                let repeat: u32 = Input::with_theme(&theme)
+                    .with_prompt("How many times did you rewatch it?")
+                    .with_initial_text(entry.repeat.to_string())
+                    .interact_text()?;
+                entry.repeat = repeat;
+                changes.insert("repeat".to_string(), json!(repeat));
+            }
+            Some(4) => {
+                entry.private = !entry.private;
+                changes.insert("private".to_string(), json!(entry.private));
+            }
+            Some(5) => {
+                entry.hidden_from_status_lists = !entry.hidden_from_status_lists;
+                changes.insert(
+                    "hiddenFromStatusLists".to_string(),
+                    json!(entry.hidden_from_status_lists),
+                );
+            }
+            Some(6) => {
+                if options.custom_lists.is_empty() {
+                    println!("You don't have any custom lists, you can make them on AniList.");
+                    continue;
+                }
+                let checked: Vec<bool> = options
+                    .custom_lists
+                    .iter()
+                    .map(|name| custom_lists.contains(name))
+                    .collect();
+                let Some(selected) = MultiSelect::with_theme(&theme)
+                    .with_prompt("Custom lists (space to toggle)")
+                    .items(&options.custom_lists)
+                    .defaults(&checked)
+                    .interact_opt()?
+                else {
+                    continue;
+                };
+                let lists: HashMap<String, bool> = options
+                    .custom_lists
+                    .iter()
+                    .enumerate()
+                    .map(|(i, name)| (name.clone(), selected.contains(&i)))
+                    .collect();
+                let names: Vec<&String> = options
+                    .custom_lists
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| selected.contains(i))
+                    .map(|(_, name)| name)
+                    .collect();
+                changes.insert("customLists".to_string(), json!(names));
+                entry.custom_lists = Some(lists);
+                utils::clear();
+            }
+            Some(7) if options.advanced_scoring_enabled => {
+                let format = score::format(client).await?;
+                let mut scores = entry.advanced_scores.clone().unwrap_or_default();
+                // * AniList wants the scores in the same order as the categories
+                let mut ordered = Vec::new();
+                for category in &options.advanced_scoring {
+                    let current = scores.get(category).copied().unwrap_or(0.0);
+                    println!("{} (now {})", style(category).bold(), current);
+                    // * Backing out keeps the score the category had
+                    let score = match score::ask(format)? {
+                        Some(raw) => format.scaled(raw),
+                        None => current,
+                    };
+                    scores.insert(category.clone(), score);
+                    ordered.push(score);
+                }
+                changes.insert("advancedScores".to_string(), json!(ordered));
+                entry.advanced_scores = Some(scores);
+                utils::clear();
+            }
+            Some(i) if i == save_index => {
+                if changes.is_empty() {
+                    println!("Nothing was changed.");
+                } else {
+                    save(client, media_id, changes).await?;
+                    println!("List entry saved!");
+                }
+                return Ok(());
+            }
+            _ => {
+                println!("Changes discarded.");
+                return Ok(());
+            }
+        }
+    }
+}
+
+// Asks for a status and puts the anime on the list with it, Planning is the default
+pub async fn ask_and_add(client: &Client, media_id: i32, title: &str) -> Result<()> {
+    let theme = theme::CustomTheme {};
+    let status = Select::with_theme(&theme)
+        .with_prompt("Select the status for the anime")
+        .items(mutation::status_names())
+        .default(4)
+        .interact_opt()?;
+    utils::clear();
+    if let Some(status) = status {
+        mutation::update_status(client, media_id, status).await?;
+        println!("Added {} as {}.", title, mutation::status_name(status));
+    }
+    Ok(())
+}
+
+// Asks whether to watch the anime right away or put it on the list
+// Returns the anime to watch, it's set to watching if it wasn't on the list yet
+// A completed anime is rewatched from the first episode instead
+pub async fn watch_or_add(
+    client: &Client,
+    media_id: i32,
+    title: &str,
+    episodes: Option<u32>,
+    entry: Option<&ListStatus>,
+) -> Result<Option<AnimeData>> {
+    let status = entry.and_then(|e| e.status.as_deref());
+    let completed = status == Some("COMPLETED");
+    let theme = theme::CustomTheme {};
+    let watch = if completed { "Rewatch now" } else { "Watch now" };
+    let options = [watch, "Add to list", "Back"];
+    let selection = Select::with_theme(&theme)
+        .with_prompt(title)
+        .items(&options)
+        .default(0)
+        .interact_opt()?;
+    utils::clear();
+
+    match selection {
+        Some(0) if completed => {
+            mutation::update_status(client, media_id, REPEATING).await?;
+            mutation::update_progress(client, media_id, 0).await?;
+            let mut db = local_save::ProgressDatabase::load()
+                .with_context(|| "Failed to load progress database")?;
+            db.clear_rewatch(media_id);
+            db.save().with_context(|| "Failed to save progress database")?;
+            Ok(Some(AnimeData {
+                id: media_id,
+                progress: 0,
+                episodes: episodes.unwrap_or(0),
+                title: title.to_string(),
+                rewatching: true,
+            }))
+        }
+        Some(0) => {
+            if status.is_none() {
+                mutation::update_status(client, media_id, CURRENT).await?;
+            }
+            Ok(Some(AnimeData {
+                id: media_id,
+                progress: entry.and_then(|e| e.progress).unwrap_or(0),
+                episodes: episodes.unwrap_or(0),
+                title: title.to_string(),
+                rewatching: status == Some("REPEATING"),
+            }))
+        }
+        Some(1) => {
+            ask_and_add(client, media_id, title).await?;
+            Ok(None)
+        }
+        _ => Ok(None),
+    }
+}
+
+fn ask_date(theme: &theme::CustomTheme, prompt: &str) -> Result<FuzzyDate> {
+    loop {
+        let input: String = Input::with_theme(theme)
+            .with_prompt(format!("{} (YYYY-MM-DD, \"today\", empty to remove)", prompt))
+            .allow_empty(true)
+            .interact_text()?;
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(FuzzyDate::default());
+        }
+        if input.eq_ignore_ascii_case("today") {
+            return Ok(FuzzyDate::today());
+        }
+        match FuzzyDate::parse(input) {
+            Some(date) => return Ok(date),
+            None => println!("That's not a valid date."),
+        }
+    }
+}
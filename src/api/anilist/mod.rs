@@ -1,3 +1,8 @@
+pub mod auth;
 pub mod mutation;
+pub mod outbox;
 pub mod fetch;
-pub mod user_fetch;
\ No newline at end of file
+pub mod graphql;
+pub mod list_entry;
+pub mod score;
+pub mod user_fetch;
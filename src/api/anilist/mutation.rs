@@ -1,167 +1,188 @@
 // Mutations
 
 // =============== Imports ================
-use crate::api::anilist::user_fetch;
+use crate::api::anilist::graphql;
+use crate::api::anilist::outbox::{self, Change, Delivery};
 
 use log;
 use reqwest::Client;
-use serde_json::json;
-use std::process;
+use serde::Deserialize;
+use serde_json::{Value, json};
 use anyhow::Result;
 
-// Constant variables
-const ANILIST_API_URL: &str = "https://graphql.anilist.co";
-// const ANILIST_CLIENT_ID: &str = "25501";
+// Indexes of the statuses used outside of the status select
+pub const CURRENT: usize = 0;
+pub const COMPLETED: usize = 1;
+pub const REPEATING: usize = 5;
+
+const STATUSES: [&str; 6] = [
+    "CURRENT",
+    "COMPLETED",
+    "PAUSED",
+    "DROPPED",
+    "PLANNING",
+    "REPEATING",
+];
+const STATUS_NAMES: [&str; 6] = [
+    "Watching",
+    "Completed",
+    "Paused",
+    "Dropped",
+    "Planning",
+    "Rewatching",
+];
+
+#[derive(Deserialize)]
+struct SaveData {
+    #[serde(rename = "SaveMediaListEntry")]
+    entry: SavedEntry,
+}
 
-// Works for both adding and modifying anime statuses
-// Takes in an id and a status index
-pub async fn update_status(client: &Client, id: i32, status_index: usize) -> Result<()> {
-    let option = vec![
-        "CURRENT",
-        "COMPLETED",
-        "PAUSED",
-        "DROPPED",
-        "PLANNING",
-        "REPEATING",
-    ];
-
-    let anilist_status = option[status_index];
-
-    let query_string = r#"
-        mutation ($mediaId: Int, $status: MediaListStatus) { 
-            SaveMediaListEntry(mediaId: $mediaId, status: $status) {
-                id
-                status
-            }
-        }
-    "#;
-
-    let variables = json!({
-        "mediaId": id,
-        "status": anilist_status
-    });
-
-    let response = client
-        .post(ANILIST_API_URL)
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", user_fetch::get_token()))
-        .json(&json!({
-            "query": query_string,
-            "variables": variables
-        }))
-        .send()
-        .await;
-
-    match response {
-        Ok(response) => {
-            let options = vec![
-                "Watching",
-                "Completed",
-                "Paused",
-                "Dropped",
-                "Planning",
-                "Rewatching",
-            ];
-            if response.status().is_success() {
-                println!(
-                    "Successfully updated anime status to {}!",
-                    options[status_index]
-                );
-                Ok(())
-            } else {
-                Err(anyhow::anyhow!("Failed to update status. Status: {}", response.status()))
-            }
-        }
-        Err(e) => {
-            Err(anyhow::anyhow!("Error updating anime status: {}", e))
-        }
-    }
+#[derive(Deserialize)]
+struct SavedEntry {
+    id: i32,
 }
 
+// Name of the status for printing, same order as the status selects
+pub fn status_name(status_index: usize) -> &'static str {
+    STATUS_NAMES.get(status_index).copied().unwrap_or("Unknown")
+}
 
-// Updates progress of selected anime to selected episode
-pub async fn update_progress(client: &Client, anime_id: i32, episode: u32) -> Result<()> {
-    let query_string = r#"
-        mutation ($mediaId: Int, $progress: Int) { 
-            SaveMediaListEntry(mediaId: $mediaId, progress: $progress) {
-                id
-                progress
-            }
-        }
-    "#;
-
-    let variables = json!({
-        "mediaId": anime_id,
-        "progress": episode as i32
-    });
-
-    let response = client
-        .post(ANILIST_API_URL)
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", user_fetch::get_token()))
-        .json(&json!({
-            "query": query_string,
-            "variables": variables
-        }))
-        .send()
-        .await;
-    match response {
-        Ok(res) => {
-            if !res.status().is_success() {
-                return Err(anyhow::anyhow!("Failed to update progress. Status: {}", res.status()))
-            }
+// Every status name, in the order the status selects use
+pub fn status_names() -> &'static [&'static str] {
+    &STATUS_NAMES
+}
+
+// Name of an AniList status (e.g. "CURRENT") for printing
+pub fn status_label(anilist_status: &str) -> &'static str {
+    STATUSES
+        .iter()
+        .position(|s| *s == anilist_status)
+        .map_or("Unknown", status_name)
+}
+
+// Every mutation is a SaveMediaListEntry with different fields, errors are logged here
+async fn save_entry(client: &Client, query_string: &str, variables: Value, what: &str) -> Result<()> {
+    let data: Result<SaveData> = graphql::authed_query(client, query_string, variables).await;
+    match data {
+        Ok(data) => {
+            log::info!("Updated {} of list entry {}", what, data.entry.id);
+            Ok(())
         }
         Err(e) => {
-            log::error!("Error updating anime progress: {}", e);
-            return Err(e.into())
+            log::error!("Error updating anime {}: {}", what, e);
+            Err(e.context(format!("Failed to update {}", what)))
         }
     }
-    Ok(())
 }
 
-// Updates score to given score of given anime
-pub async fn update_score(client: &Client, anime_id: i32, score: f64) -> Result<(), anyhow::Error> {
-    let query_string = r#"
-        mutation ($mediaId: Int, $score: Float) { 
-            SaveMediaListEntry(mediaId: $mediaId, score: $score) {
-                id
-                score
-            }
+// Sends a single change to AniList, without queueing (the outbox uses this)
+pub async fn apply(client: &Client, media_id: i32, change: &Change) -> Result<()> {
+    match change {
+        Change::Status { status_index } => {
+            let anilist_status = STATUSES
+                .get(*status_index)
+                .ok_or_else(|| anyhow::anyhow!("Unknown status index: {}", status_index))?;
+
+            let query_string = r#"
+                mutation ($mediaId: Int, $status: MediaListStatus) { 
+                    SaveMediaListEntry(mediaId: $mediaId, status: $status) {
+                        id
+                        status
+                    }
+                }
+            "#;
+            let variables = json!({
+                "mediaId": media_id,
+                "status": anilist_status
+            });
+            save_entry(client, query_string, variables, "status").await
         }
-    "#;
-
-    let variables = serde_json::json!({"score": score, "mediaId": anime_id });
-    let response = client
-        .post(ANILIST_API_URL)
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", user_fetch::get_token()))
-        .json(&json!({
-            "query": query_string,
-            "variables": variables
-        }))
-        .send()
-        .await;
-
-    match response {
-        Ok(res) => {
-            if res.status().is_success() {
-                println!("Successfully updated anime score to {}!", score);
-            } else {
-                eprintln!("Failed to update score. Status: {}", res.status());
-                log::error!("Failed to update score. Status: {}", res.status());
-                process::exit(1);
-            }
-            Ok(())
+        Change::Progress { episode } => {
+            let query_string = r#"
+                mutation ($mediaId: Int, $progress: Int) { 
+                    SaveMediaListEntry(mediaId: $mediaId, progress: $progress) {
+                        id
+                        progress
+                    }
+                }
+            "#;
+            let variables = json!({
+                "mediaId": media_id,
+                "progress": *episode as i32
+            });
+            save_entry(client, query_string, variables, "progress").await
         }
-        Err(e) => {
-            if e.is_timeout() {
-                log::warn!("Internet connection error");
-                Err(anyhow::anyhow!("Internet connection error"))
-            } else {
-                log::error!("Error updating score: {}", e);
-                Err(e.into())
-            }
+        Change::Score { raw } => {
+            // * scoreRaw is always 0-100, AniList converts it to the user's format
+            let query_string = r#"
+                mutation ($mediaId: Int, $scoreRaw: Int) { 
+                    SaveMediaListEntry(mediaId: $mediaId, scoreRaw: $scoreRaw) {
+                        id
+                        score
+                    }
+                }
+            "#;
+            let variables = json!({"scoreRaw": raw, "mediaId": media_id });
+            save_entry(client, query_string, variables, "score").await
+        }
+        Change::StartedAt { date } => {
+            let query_string = r#"
+                mutation ($mediaId: Int, $startedAt: FuzzyDateInput) { 
+                    SaveMediaListEntry(mediaId: $mediaId, startedAt: $startedAt) {
+                        id
+                    }
+                }
+            "#;
+            let variables = json!({"startedAt": date, "mediaId": media_id });
+            save_entry(client, query_string, variables, "start date").await
+        }
+        Change::CompletedAt { date } => {
+            let query_string = r#"
+                mutation ($mediaId: Int, $completedAt: FuzzyDateInput) { 
+                    SaveMediaListEntry(mediaId: $mediaId, completedAt: $completedAt) {
+                        id
+                    }
+                }
+            "#;
+            let variables = json!({"completedAt": date, "mediaId": media_id });
+            save_entry(client, query_string, variables, "completion date").await
+        }
+        Change::Repeat { count } => {
+            let query_string = r#"
+                mutation ($mediaId: Int, $repeat: Int) { 
+                    SaveMediaListEntry(mediaId: $mediaId, repeat: $repeat) {
+                        id
+                        repeat
+                    }
+                }
+            "#;
+            let variables = json!({"repeat": count, "mediaId": media_id });
+            save_entry(client, query_string, variables, "rewatch count").await
         }
     }
 }
 
+// Works for both adding and modifying anime statuses
+// Takes in an id and a status index
+pub async fn update_status(client: &Client, id: i32, status_index: usize) -> Result<Delivery> {
+    let delivery = outbox::submit(client, id, Change::Status { status_index }).await?;
+    if delivery == Delivery::Sent {
+        println!(
+            "Successfully updated anime status to {}!",
+            status_name(status_index)
+        );
+    }
+    Ok(delivery)
+}
+
+
+// Updates progress of selected anime to selected episode
+pub async fn update_progress(client: &Client, anime_id: i32, episode: u32) -> Result<Delivery> {
+    outbox::submit(client, anime_id, Change::Progress { episode }).await
+}
+
+// Updates score of given anime, the score is raw (0-100), see the score module
+pub async fn update_score(client: &Client, anime_id: i32, raw: u32) -> Result<Delivery> {
+    outbox::submit(client, anime_id, Change::Score { raw }).await
+}
@@ -0,0 +1,389 @@
+// Outbox for AniList updates that couldn't be sent
+// If AniList can't be reached the update is saved and sent later, so no progress is lost
+// Only one update is kept per anime and kind, the newest one wins
+
+// =============== Imports ================
+use crate::api::anilist::auth;
+use crate::api::anilist::fetch;
+use crate::api::anilist::graphql::{AniListError, FuzzyDate};
+use crate::api::anilist::mutation;
+use crate::profile;
+use crate::theme;
+use crate::utils;
+
+use anyhow::{Context, Result};
+use console::style;
+use dialoguer::Select;
+use futures::future::join_all;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tokio::time::{Duration, sleep};
+
+// After this many tries the update is marked as failed and has to be retried by hand
+const MAX_ATTEMPTS: u32 = 10;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+// * Guards reading and writing the outbox file, never held across an await
+static FILE_LOCK: Mutex<()> = Mutex::new(());
+// * Only one flush at a time, so nothing is sent twice
+static FLUSH_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Change {
+    Progress { episode: u32 },
+    Status { status_index: usize },
+    Score { raw: u32 }, // 0-100, whatever the user's score format is
+    StartedAt { date: FuzzyDate },
+    CompletedAt { date: FuzzyDate },
+    Repeat { count: u32 },
+}
+
+impl Change {
+    fn same_kind(&self, other: &Change) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Progress { episode } => write!(f, "progress -> {}", episode),
+            Change::Status { status_index } => {
+                write!(f, "status -> {}", mutation::status_name(*status_index))
+            }
+            Change::Score { raw } => write!(f, "score -> {}/100", raw),
+            Change::StartedAt { date } => write!(f, "started -> {}", date),
+            Change::CompletedAt { date } => write!(f, "completed -> {}", date),
+            Change::Repeat { count } => write!(f, "rewatches -> {}", count),
+        }
+    }
+}
+
+// Whether the update reached AniList right away or is waiting in the outbox
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Delivery {
+    Sent,
+    Queued,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Pending {
+    media_id: i32,
+    change: Change,
+    queued_at: u64,
+    #[serde(default)]
+    attempts: u32,
+    #[serde(default)]
+    last_error: Option<String>,
+    #[serde(default)]
+    failed: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct Outbox {
+    entries: Vec<Pending>,
+}
+
+// Sends the change, queues it if AniList can't be reached right now
+pub async fn submit(client: &Client, media_id: i32, change: Change) -> Result<Delivery> {
+    match mutation::apply(client, media_id, &change).await {
+        Ok(()) => {
+            // * A newer update went through, so the queued one is outdated
+            modify(|outbox| {
+                outbox
+                    .entries
+                    .retain(|p| !(p.media_id == media_id && p.change.same_kind(&change)))
+            })?;
+            Ok(Delivery::Sent)
+        }
+        Err(e) if is_transient(&e) => {
+            log::warn!("Queueing AniList update for {} ({}): {:#}", media_id, change, e);
+            enqueue(media_id, change)?;
+            println!(
+                "{}",
+                style("AniList can't be reached, the update will be sent later.").yellow()
+            );
+            Ok(Delivery::Queued)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+// Sends everything waiting in the outbox, returns how many went through
+pub async fn flush(client: &Client) -> Result<usize> {
+    let _guard = FLUSH_LOCK.lock().await;
+    let pending: Vec<Pending> = load()?.entries.into_iter().filter(|p| !p.failed).collect();
+    if pending.is_empty() {
+        return Ok(0);
+    }
+    // * Flushing also runs in the background, so it never asks to log in
+    if auth::existing_token().is_none() {
+        return Err(anyhow::anyhow!(
+            "Not logged in to AniList, the queued updates wait for a login"
+        ));
+    }
+    log::info!("Flushing {} queued AniList update(s)", pending.len());
+
+    let mut sent = 0;
+    for item in pending {
+        let result = mutation::apply(client, item.media_id, &item.change).await;
+        let transient = result.as_ref().err().is_some_and(is_transient);
+        if let Err(e) = &result {
+            log::warn!("Queued update for {} failed: {:#}", item.media_id, e);
+        } else {
+            sent += 1;
+        }
+
+        modify(|outbox| {
+            // * The entry could have been replaced by a newer update while this one was sent
+            let Some(index) = outbox
+                .entries
+                .iter()
+                .position(|p| p.media_id == item.media_id && p.change == item.change)
+            else {
+                return;
+            };
+            match &result {
+                Ok(()) => {
+                    outbox.entries.remove(index);
+                }
+                Err(e) => {
+                    let entry = &mut outbox.entries[index];
+                    entry.attempts += 1;
+                    entry.last_error = Some(format!("{:#}", e));
+                    entry.failed = !transient || entry.attempts >= MAX_ATTEMPTS;
+                }
+            }
+        })?;
+
+        // No point in trying the rest if AniList is still down
+        if transient {
+            break;
+        }
+    }
+    Ok(sent)
+}
+
+// Flushes at startup and tells the user about it
+pub async fn flush_on_start(client: &Client) {
+    match flush(client).await {
+        Ok(0) => {}
+        Ok(sent) => println!("Sent {} queued AniList update(s).", sent),
+        Err(e) => log::error!("Failed to flush the outbox: {:#}", e),
+    }
+    if let Ok(outbox) = load() {
+        let failed = outbox.entries.iter().filter(|p| p.failed).count();
+        if failed > 0 {
+            println!(
+                "{}",
+                style(format!(
+                    "{} AniList update(s) failed, check them with yato --outbox",
+                    failed
+                ))
+                .red()
+            );
+        }
+    }
+}
+
+// Keeps trying in the background while yato is open
+pub fn spawn_background_flush(client: Client) {
+    tokio::spawn(async move {
+        loop {
+            sleep(FLUSH_INTERVAL).await;
+            if pending_count() == 0 {
+                continue;
+            }
+            if let Err(e) = flush(&client).await {
+                log::error!("Background outbox flush failed: {:#}", e);
+            }
+        }
+    });
+}
+
+fn age(queued_at: u64) -> String {
+    let seconds = utils::now().saturating_sub(queued_at);
+    match seconds {
+        0..=3599 => format!("{} min", seconds / 60),
+        3600..=86399 => format!("{} h", seconds / 3600),
+        _ => format!("{} days", seconds / 86400),
+    }
+}
+
+fn pending_count() -> usize {
+    load()
+        .map(|o| o.entries.iter().filter(|p| !p.failed).count())
+        .unwrap_or(0)
+}
+
+// Shows what's waiting and lets the user retry or drop them
+pub async fn interactive(client: &Client) -> Result<()> {
+    let outbox = load()?;
+    if outbox.entries.is_empty() {
+        println!("There are no queued AniList updates.");
+        return Ok(());
+    }
+
+    let titles = join_all(
+        outbox
+            .entries
+            .iter()
+            .map(|p| fetch::data_by_id(client, p.media_id)),
+    )
+    .await;
+
+    println!("{}", style("Queued AniList updates").bold().underlined());
+    for (item, title) in outbox.entries.iter().zip(titles) {
+        let title = title
+            .map(|data| data.title)
+            .unwrap_or_else(|_| format!("<{}>", item.media_id));
+        let state = if item.failed {
+            style("failed").red()
+        } else {
+            style("pending").yellow()
+        };
+        println!(
+            "{} | {} | {} | queued {} ago | tries: {}",
+            state,
+            title,
+            item.change,
+            age(item.queued_at),
+            item.attempts
+        );
+        if let Some(error) = &item.last_error {
+            println!("    {}", style(error).dim());
+        }
+    }
+    println!();
+
+    let options = ["Retry everything now", "Remove failed updates", "Remove everything", "Back"];
+    let theme = theme::CustomTheme {};
+    let selection = Select::with_theme(&theme)
+        .with_prompt("What should happen with them?")
+        .items(&options)
+        .default(0)
+        .interact_opt()?;
+    utils::clear();
+
+    match selection {
+        Some(0) => {
+            modify(|outbox| {
+                for entry in outbox.entries.iter_mut() {
+                    entry.failed = false;
+                    entry.attempts = 0;
+                }
+            })?;
+            let sent = flush(client).await?;
+            let left = load()?.entries.len();
+            println!("Sent {} update(s), {} still queued.", sent, left);
+        }
+        Some(1) => {
+            modify(|outbox| outbox.entries.retain(|p| !p.failed))?;
+            println!("Removed failed updates.");
+        }
+        Some(2) => {
+            modify(|outbox| outbox.entries.clear())?;
+            println!("Removed every queued update.");
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// Connection problems, timeouts, rate limits and AniList being down are worth retrying, everything else is not
+fn is_transient(e: &anyhow::Error) -> bool {
+    let retryable = |status: u16| status == 429 || status >= 500;
+    e.chain().any(|cause| {
+        if let Some(err) = cause.downcast_ref::<AniListError>() {
+            return err.status.is_some_and(retryable);
+        }
+        if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+            return err.is_connect()
+                || err.is_timeout()
+                || err.status().is_some_and(|s| retryable(s.as_u16()));
+        }
+        false
+    })
+}
+
+fn enqueue(media_id: i32, change: Change) -> Result<()> {
+    modify(|outbox| {
+        let pending = Pending {
+            media_id,
+            change: change.clone(),
+            queued_at: utils::now(),
+            attempts: 0,
+            last_error: None,
+            failed: false,
+        };
+        // * A newer change replaces the queued one of the same kind and goes to the back, keeping the queue in order
+        outbox
+            .entries
+            .retain(|p| !(p.media_id == media_id && p.change.same_kind(&change)));
+        outbox.entries.push(pending);
+    })
+}
+
+// =============== Storage ===============
+fn path() -> Result<PathBuf> {
+    Ok(profile::data_dir()?.join("outbox.json"))
+}
+
+fn load() -> Result<Outbox> {
+    let _guard = FILE_LOCK.lock().unwrap();
+    read()
+}
+
+fn read() -> Result<Outbox> {
+    let path = path()?;
+    if !path.exists() {
+        return Ok(Outbox::default());
+    }
+    let json = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read outbox: {:?}", path))?;
+    serde_json::from_str(&json).with_context(|| "Failed to parse outbox JSON")
+}
+
+// Loads, changes and saves the outbox in one go
+fn modify(f: impl FnOnce(&mut Outbox)) -> Result<()> {
+    let _guard = FILE_LOCK.lock().unwrap();
+    let mut outbox = read()?;
+    f(&mut outbox);
+    let path = path()?;
+    if outbox.entries.is_empty() && !path.exists() {
+        return Ok(());
+    }
+    let json = serde_json::to_string_pretty(&outbox)?;
+    fs::write(&path, json).with_context(|| format!("Failed to write outbox: {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anilist_error(status: Option<u16>) -> anyhow::Error {
+        anyhow::Error::from(AniListError {
+            status,
+            message: "error".to_string(),
+        })
+    }
+
+    #[test]
+    fn rate_limits_and_server_errors_are_transient() {
+        assert!(is_transient(&anilist_error(Some(429))));
+        assert!(is_transient(&anilist_error(Some(503))));
+        assert!(is_transient(&anilist_error(Some(500)).context("Failed to update progress")));
+    }
+
+    #[test]
+    fn other_errors_are_not_transient() {
+        assert!(!is_transient(&anilist_error(Some(400))));
+        assert!(!is_transient(&anilist_error(Some(404))));
+        assert!(!is_transient(&anilist_error(None)));
+        assert!(!is_transient(&anyhow::anyhow!("Failed to parse AniList response")));
+    }
+}
@@ -0,0 +1,262 @@
+// Scores in the format the user chose on AniList
+// AniList keeps every score as 0-100 internally (scoreRaw), we only convert for showing and asking
+
+// =============== Imports ================
+use crate::api::anilist::graphql;
+use crate::api::anilist::mutation;
+use crate::api::anilist::outbox::Delivery;
+use crate::theme;
+use crate::utils;
+
+use anyhow::Result;
+use dialoguer::{Input, Select};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use std::fmt;
+use std::sync::Mutex;
+
+// * Asked once per run, it doesn't change while yato is open
+static FORMAT: Mutex<Option<ScoreFormat>> = Mutex::new(None);
+
+const SMILEYS: [&str; 3] = [":(", ":|", ":)"];
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ScoreFormat {
+    #[serde(rename = "POINT_100")]
+    Point100,
+    #[serde(rename = "POINT_10_DECIMAL")]
+    Point10Decimal,
+    #[serde(rename = "POINT_10")]
+    Point10,
+    #[serde(rename = "POINT_5")]
+    Point5,
+    #[serde(rename = "POINT_3")]
+    Point3,
+}
+
+impl ScoreFormat {
+    // Raw 0-100 score as the user would see it on AniList
+    pub fn display(self, raw: u32) -> String {
+        if raw == 0 {
+            return "Not yet scored".to_string();
+        }
+        match self {
+            ScoreFormat::Point100 => format!("{}/100", raw),
+            ScoreFormat::Point10Decimal => format!("{:.1}/10", raw as f64 / 10.0),
+            ScoreFormat::Point10 => format!("{}/10", (raw + 5) / 10),
+            ScoreFormat::Point5 => {
+                let stars = ((raw + 10) / 20).clamp(1, 5) as usize;
+                format!("{}{}", "★".repeat(stars), "☆".repeat(5 - stars))
+            }
+            ScoreFormat::Point3 => SMILEYS[smiley_index(raw)].to_string(),
+        }
+    }
+
+    // Raw 0-100 score in the format's own scale, the way AniList keeps advanced scores
+    pub fn scaled(self, raw: u32) -> f64 {
+        match self {
+            ScoreFormat::Point100 => raw as f64,
+            ScoreFormat::Point10Decimal => raw as f64 / 10.0,
+            ScoreFormat::Point10 => ((raw + 5) / 10) as f64,
+            ScoreFormat::Point5 => ((raw + 10) / 20) as f64,
+            ScoreFormat::Point3 => (smiley_index(raw) + 1) as f64,
+        }
+    }
+}
+
+impl fmt::Display for ScoreFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            ScoreFormat::Point100 => "100 point",
+            ScoreFormat::Point10Decimal => "10 point decimal",
+            ScoreFormat::Point10 => "10 point",
+            ScoreFormat::Point5 => "5 star",
+            ScoreFormat::Point3 => "3 point smiley",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+// Same borders AniList uses when it shows raw scores as smileys
+fn smiley_index(raw: u32) -> usize {
+    match raw {
+        0..=35 => 0,
+        36..=60 => 1,
+        _ => 2,
+    }
+}
+
+#[derive(Deserialize)]
+struct ViewerData {
+    #[serde(rename = "Viewer")]
+    viewer: Viewer,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Viewer {
+    media_list_options: MediaListOptions,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MediaListOptions {
+    score_format: Option<ScoreFormat>,
+}
+
+// The score format of the logged in user
+pub async fn format(client: &Client) -> Result<ScoreFormat> {
+    if let Some(format) = *FORMAT.lock().unwrap() {
+        return Ok(format);
+    }
+
+    let query_string = r#"
+        query {
+            Viewer {
+                mediaListOptions {
+                    scoreFormat
+                }
+            }
+        }
+    "#;
+    let data: ViewerData = graphql::authed_query(client, query_string, json!({})).await?;
+    let format = data
+        .viewer
+        .media_list_options
+        .score_format
+        .unwrap_or(ScoreFormat::Point10);
+    log::info!("User's score format: {}", format);
+    *FORMAT.lock().unwrap() = Some(format);
+    Ok(format)
+}
+
+// Asks for a score in the given format, returns it as a raw 0-100 score
+// None if the user backed out
+pub fn ask(format: ScoreFormat) -> Result<Option<u32>> {
+    let theme = theme::CustomTheme {};
+    let raw = match format {
+        ScoreFormat::Point5 => {
+            let options: Vec<String> = (1..=5).map(|stars| format.display(stars * 20)).collect();
+            Select::with_theme(&theme)
+                .with_prompt("How many stars?")
+                .items(&options)
+                .default(4)
+                .interact_opt()?
+                .map(|index| choice_score(format, index))
+        }
+        ScoreFormat::Point3 => Select::with_theme(&theme)
+            .with_prompt("How was it?")
+            .items(&SMILEYS)
+            .default(2)
+            .interact_opt()?
+            .map(|index| choice_score(format, index)),
+        ScoreFormat::Point100 => {
+            let score = ask_number(&theme, "Enter a score on a scale of 1 to 100", 1.0, 100.0)?;
+            Some(number_score(format, score))
+        }
+        ScoreFormat::Point10 => {
+            let score = ask_number(&theme, "Enter a score on a scale of 1 to 10", 1.0, 10.0)?;
+            Some(number_score(format, score))
+        }
+        ScoreFormat::Point10Decimal => {
+            let score = ask_number(&theme, "Enter a score on a scale of 1 to 10 (e.g. 7.5)", 1.0, 10.0)?;
+            Some(number_score(format, score))
+        }
+    };
+    utils::clear();
+    Ok(raw)
+}
+
+// Raw score of the option picked for stars and smileys
+fn choice_score(format: ScoreFormat, index: usize) -> u32 {
+    match format {
+        ScoreFormat::Point3 => [35, 60, 85][index],
+        _ => (index as u32 + 1) * 20,
+    }
+}
+
+// Raw score of a number typed in on the format's scale
+fn number_score(format: ScoreFormat, score: f64) -> u32 {
+    match format {
+        ScoreFormat::Point10 => score.round() as u32 * 10,
+        ScoreFormat::Point10Decimal => (score * 10.0).round() as u32,
+        _ => score.round() as u32,
+    }
+}
+
+fn ask_number(theme: &theme::CustomTheme, prompt: &str, min: f64, max: f64) -> Result<f64> {
+    loop {
+        let input: String = Input::with_theme(theme).with_prompt(prompt).interact_text()?;
+        match input.trim().replace(',', ".").parse::<f64>() {
+            Ok(score) if (min..=max).contains(&score) => return Ok(score),
+            _ => println!("Score must be between {} and {}.", min, max),
+        }
+    }
+}
+
+// Asks for a score in the user's format and saves it
+pub async fn ask_and_update(client: &Client, anime_id: i32) -> Result<()> {
+    let format = format(client).await?;
+    let Some(raw) = ask(format)? else {
+        return Err(anyhow::anyhow!("No score was given"));
+    };
+    if mutation::update_score(client, anime_id, raw).await? == Delivery::Sent {
+        println!("Successfully updated anime score to {}!", format.display(raw));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn displays_raw_scores_in_every_format() {
+        assert_eq!(ScoreFormat::Point100.display(85), "85/100");
+        assert_eq!(ScoreFormat::Point10Decimal.display(85), "8.5/10");
+        assert_eq!(ScoreFormat::Point10.display(85), "9/10");
+        assert_eq!(ScoreFormat::Point10.display(84), "8/10");
+        assert_eq!(ScoreFormat::Point5.display(60), "★★★☆☆");
+        assert_eq!(ScoreFormat::Point5.display(5), "★☆☆☆☆");
+        assert_eq!(ScoreFormat::Point3.display(35), ":(");
+        assert_eq!(ScoreFormat::Point3.display(60), ":|");
+        assert_eq!(ScoreFormat::Point3.display(61), ":)");
+        assert_eq!(ScoreFormat::Point3.display(0), "Not yet scored");
+    }
+
+    #[test]
+    fn typed_scores_become_raw_scores() {
+        assert_eq!(number_score(ScoreFormat::Point100, 73.0), 73);
+        assert_eq!(number_score(ScoreFormat::Point10, 7.0), 70);
+        assert_eq!(number_score(ScoreFormat::Point10, 7.6), 80);
+        assert_eq!(number_score(ScoreFormat::Point10Decimal, 7.5), 75);
+    }
+
+    #[test]
+    fn picked_options_show_as_picked() {
+        for (index, smiley) in SMILEYS.iter().enumerate() {
+            assert_eq!(ScoreFormat::Point3.display(choice_score(ScoreFormat::Point3, index)), *smiley);
+        }
+        for stars in 1..=5 {
+            let shown = ScoreFormat::Point5.display(choice_score(ScoreFormat::Point5, stars - 1));
+            assert_eq!(shown.matches('★').count(), stars);
+        }
+    }
+
+    #[test]
+    fn advanced_scores_use_the_format_scale() {
+        assert_eq!(ScoreFormat::Point100.scaled(85), 85.0);
+        assert_eq!(ScoreFormat::Point10Decimal.scaled(85), 8.5);
+        assert_eq!(ScoreFormat::Point10.scaled(85), 9.0);
+        assert_eq!(ScoreFormat::Point5.scaled(60), 3.0);
+        assert_eq!(ScoreFormat::Point3.scaled(85), 3.0);
+    }
+
+    #[test]
+    fn reads_anilist_format_names() {
+        let format: ScoreFormat = serde_json::from_str("\"POINT_10_DECIMAL\"").unwrap();
+        assert_eq!(format, ScoreFormat::Point10Decimal);
+        assert_eq!(format.to_string(), "10 point decimal");
+    }
+}
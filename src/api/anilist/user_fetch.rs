@@ -1,25 +1,75 @@
 // Getting user related data
 
 // =============== Imports ================
+use crate::api::anilist::auth;
+use crate::api::anilist::graphql::{self, AniListError, CoverImage, NextAiring, Title};
+use crate::api::anilist::score::{self, ScoreFormat};
+use crate::cover;
+use crate::profile;
 use crate::skip_override;
 use crate::theme;
 use crate::utils;
 
 use anyhow::{Context, Result};
-use dialoguer::{FuzzySelect, Input};
+use dialoguer::FuzzySelect;
 use log;
 use reqwest::Client;
-use serde_json::{Value, json};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashSet;
 use std::{fs, process};
 
-// Constant variables
-const ANILIST_API_URL: &str = "https://graphql.anilist.co";
-const ANILIST_CLIENT_ID: &str = "25501";
+#[derive(Deserialize)]
+struct ViewerData {
+    #[serde(rename = "Viewer")]
+    viewer: Viewer,
+}
+
+#[derive(Deserialize)]
+struct Viewer {
+    id: i32,
+}
+
+#[derive(Deserialize)]
+struct ListData {
+    #[serde(rename = "MediaListCollection")]
+    collection: ListCollection,
+}
+
+#[derive(Deserialize)]
+struct ListCollection {
+    lists: Vec<MediaList>,
+}
+
+#[derive(Deserialize)]
+struct MediaList {
+    #[serde(default)]
+    entries: Vec<ListEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListEntry {
+    score: Option<f64>,
+    status: Option<String>,
+    progress: Option<u32>,
+    updated_at: Option<u64>,
+    media: ListMedia,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListMedia {
+    id: i32,
+    title: Title,
+    episodes: Option<u32>,
+    next_airing_episode: Option<NextAiring>,
+    cover_image: Option<CoverImage>,
+}
 
 // Checks if token is valid
 pub async fn check_credentials(client: &Client) -> Result<()> {
     log::info!("Checking AniList credentials");
-    let auth = get_token();
 
     let query = r#"
         query {
@@ -30,91 +80,39 @@ pub async fn check_credentials(client: &Client) -> Result<()> {
         }
     "#;
 
-    let body = serde_json::json!({
-        "query": query
-    });
-
-    let response = client
-        .post(ANILIST_API_URL)
-        .header("Authorization", format!("Bearer {}", auth))
-        .header("Content-Type", "application/json")
-        .json(&body)
-        .send()
-        .await
-        .with_context(|| "Failed to send request to AniList API")?;
-
-    if response.status().is_success() {
-        let data: Value = response
-            .json()
-            .await
-            .with_context(|| "Failed to parse JSON response from AniList API")?;
-
-        if let Some(user_id) = data["data"]["Viewer"]["id"].as_i64() {
-            let client_file = dirs::data_local_dir()
-                .ok_or_else(|| anyhow::anyhow!("Failed to get local data directory"))?
-                .join("yato/anilist_user_id");
-
-            // Write the user ID to the file
-            fs::write(&client_file, user_id.to_string())
-                .with_context(|| format!("Failed to write client ID to file: {:?}", client_file))?;
-
-            log::info!("AniList credentials verified successfully");
-            Ok(())
-        } else {
-            log::error!("Invalid or expired token");
-            remove_token_file()?;
-            Err(anyhow::anyhow!("Invalid or expired token"))
+    let data: ViewerData = match graphql::authed_query(client, query, json!({})).await {
+        Ok(data) => data,
+        Err(e) => {
+            // * Only throw away the token if AniList said it's bad, not on network errors
+            let invalid = e
+                .downcast_ref::<AniListError>()
+                .is_some_and(|err| err.is_invalid_token());
+            if invalid && auth::from_env() {
+                log::error!("Token from the environment is invalid: {}", e);
+                return Err(e.context("The token in YATO_ANILIST_TOKEN is invalid"));
+            } else if invalid {
+                log::error!("Invalid or expired token: {}", e);
+                auth::logout()?;
+            } else {
+                log::error!("Token check failed: {}", e);
+            }
+            return Err(e.context("Token check failed"));
         }
-    } else {
-        log::error!("Token check failed");
-        remove_token_file()?;
-        Err(anyhow::anyhow!("Token check failed"))
-    }
-}
+    };
 
-// Retrieves the token from the file
-pub fn get_token() -> String {
-    let data_dir = dirs::data_local_dir().unwrap().join("yato");
-    if !data_dir.exists() {
-        fs::create_dir_all(&data_dir).unwrap();
-    }
-    let auth_file = data_dir.join("anilist_token");
-    if auth_file.exists() {
-        // remove the token file if it is empty
-        if auth_file.metadata().unwrap().len() == 0 {
-            fs::remove_file(&auth_file).unwrap();
-            println!("Token file is empty. Please enter it again.");
-            log::warn!("Token file is empty, deleting file for new token.");
-            process::exit(1);
-        }
-        // Read the auth code from the file
-        let token = std::fs::read_to_string(auth_file).unwrap();
-        return token;
-    } else {
-        // Create the auth code file
-        std::fs::File::create(&auth_file).unwrap();
-        let prompt = format!(
-            "Please enter your Anilist access token generated here:\nhttps://anilist.co/api/v2/oauth/authorize?client_id={}&response_type=token",
-            ANILIST_CLIENT_ID
-        );
-        let token: String = Input::new().with_prompt(prompt).interact().unwrap();
-        fs::write(auth_file, &token).unwrap();
-        return token;
-    }
-}
+    let client_file = profile::data_dir()?.join("anilist_user_id");
 
-// Removes the token file (used in checking credentials)
-pub fn remove_token_file() -> Result<()> {
-    let auth_file = dirs::config_dir().unwrap().join("yato/anilist_token");
-    if auth_file.exists() {
-        fs::remove_file(&auth_file)?;
-    }
+    // Write the user ID to the file
+    fs::write(&client_file, data.viewer.id.to_string())
+        .with_context(|| format!("Failed to write client ID to file: {:?}", client_file))?;
+
+    log::info!("AniList credentials verified successfully");
     Ok(())
 }
 
 // Retrieves the user ID from the file
 pub fn get_id() -> Result<i32> {
-    let client_file = dirs::data_local_dir().unwrap().join("yato/anilist_user_id");
+    let client_file = profile::data_dir()?.join("anilist_user_id");
     let id: i32 = fs::read_to_string(client_file)?.parse()?;
     return Ok(id);
 }
@@ -129,13 +127,14 @@ pub async fn list_all(client: &Client, val: u8) -> Result<i32> {
                 lists {
                     name
                     entries {
-                        score
+                        score(format: POINT_100)
                         status
                         media {
                             id
                             title {
                                 romaji
                                 english
+                                native
                             }
                         }
                     }
@@ -144,125 +143,87 @@ pub async fn list_all(client: &Client, val: u8) -> Result<i32> {
         }
     "#;
 
-    let variables = serde_json::json!({"userId": user_id as i32});
-    let response = client
-        .post(ANILIST_API_URL)
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", get_token()))
-        .json(&json!({
-            "query": query_string,
-            "variables": variables
-        }))
-        .send()
-        .await;
-
-    match response {
-        Ok(res) => {
-            if res.status().is_success() {
-                let data: Value = res.json().await.expect("Failed to parse response");
-
-                let lists = data["data"]["MediaListCollection"]["lists"]
-                    .as_array()
-                    .expect("Expected 'lists' to be an array");
-
-                let mut anime_list = Vec::new();
-                for list in lists {
-                    if let Some(entries) = list["entries"].as_array() {
-                        for entry in entries {
-                            anime_list.push(entry);
-                        }
-                    }
-                }
-
-                if anime_list.is_empty() {
-                    println!("Start watching something new");
-                    process::exit(0);
-                }
-
-                let options: Vec<String> = anime_list
-                    .iter()
-                    .map(|anime| {
-                        let media = &anime["media"];
-                        let title = media["title"]["english"]
-                            .as_str()
-                            .or_else(|| media["title"]["romaji"].as_str())
-                            .unwrap_or("Unknown Title")
-                            .to_string();
-
-                        let status = &anime["status"]
-                            .as_str()
-                            .unwrap_or("Unknown Status")
-                            .to_string();
-
-                        let mut score = anime["score"]
-                            .as_u64()
-                            .map(|s| s.to_string())
-                            .unwrap_or("?".to_string());
-
-                        if score == "0" {
-                            score = "Not yet scored".to_string();
-                        }
+    let variables = json!({"userId": user_id});
+    let data: ListData = graphql::authed_query(client, query_string, variables)
+        .await
+        .map_err(|e| {
+            eprintln!("Error retrieving data: {}", e);
+            log::error!("Error retrieving data: {}", e);
+            e
+        })?;
+
+    let anime_list: Vec<ListEntry> = data
+        .collection
+        .lists
+        .into_iter()
+        .flat_map(|list| list.entries)
+        .collect();
+
+    if anime_list.is_empty() {
+        println!("Start watching something new");
+        process::exit(0);
+    }
 
-                        let id = anime["media"]["id"].as_i64().unwrap_or(0) as i32;
-                        let override_setting = skip_override::search(id);
-                        let intro = override_setting.intro;
-                        let outro = override_setting.outro;
-                        let recap = override_setting.recap;
-                        let filler = override_setting.filler;
-
-                        match val {
-                            0 => format!(
-                                "{} - Current status: {}",
-                                title,
-                                status
-                                    .replace("CURRENT", "Watching")
-                                    .replace("DROPPED", "Dropped")
-                                    .replace("REPEATING", "Rewatching")
-                                    .replace("COMPLETED", "Completed")
-                                    .replace("PAUSED", "Paused")
-                                    .replace("PLANNING", "Planning")
-                            ), // For status updating
-                            1 => format!("{} - Score: {}", title, score), // For score updating
-                            2 => format!(
-                                "{} - opening: {} | credits: {} | recap: {} | filler: {}",
-                                title, intro, outro, recap, filler
-                            ), // For override updating
-                            _ => format!("{}", title),                    // For everything else
-                        }
-                    })
-                    .collect();
-
-                let theme = theme::CustomTheme {};
-                let selected_index = FuzzySelect::with_theme(&theme)
-                    .with_prompt("Choose an anime:")
-                    .items(&options)
-                    .default(0)
-                    .clear(true)
-                    .interact_opt()
-                    .unwrap();
-                utils::clear();
-                if let Some(index) = selected_index {
-                    Ok(anime_list[index]["media"]["id"]
-                        .as_i64()
-                        .expect("No ID found") as i32)
-                } else {
-                    return Err(anyhow::anyhow!("No selection was made"))
-                }
-            } else {
-                log::error!("Failed to fetch data: {}", res.status());
-                Err(anyhow::anyhow!("Failed to fetch data: {}", res.status()))
-            }
-        }
-        Err(e) => {
-            if e.is_timeout() {
-                log::warn!("Internet connection error");
-                Err(anyhow::anyhow!("Internet connection error"))
-            } else {
-                eprintln!("Error retrieving data: {}", e);
-                log::error!("Error retrieving data: {}", e);
-                Err(e.into())
+    // * Scores come as 0-100, they are shown in the user's own format
+    let score_format = if val == 1 {
+        score::format(client).await?
+    } else {
+        ScoreFormat::Point100
+    };
+
+    let options: Vec<String> = anime_list
+        .iter()
+        .map(|anime| {
+            let title = anime.media.title.preferred();
+
+            let status = anime.status.as_deref().unwrap_or("Unknown Status");
+
+            let score = match anime.score {
+                Some(s) => score_format.display(s.round() as u32),
+                None => "?".to_string(),
+            };
+
+            let override_setting = skip_override::search(anime.media.id);
+            let intro = override_setting.intro;
+            let outro = override_setting.outro;
+            let recap = override_setting.recap;
+            let filler = override_setting.filler_label();
+            let recap_episodes = override_setting.recap_episodes;
+
+            match val {
+                0 => format!(
+                    "{} - Current status: {}",
+                    title,
+                    status
+                        .replace("CURRENT", "Watching")
+                        .replace("DROPPED", "Dropped")
+                        .replace("REPEATING", "Rewatching")
+                        .replace("COMPLETED", "Completed")
+                        .replace("PAUSED", "Paused")
+                        .replace("PLANNING", "Planning")
+                ), // For status updating
+                1 => format!("{} - Score: {}", title, score), // For score updating
+                2 => format!(
+                    "{} - opening: {} | credits: {} | recap: {} | filler: {} | recap episodes: {}",
+                    title, intro, outro, recap, filler, recap_episodes
+                ), // For override updating
+                _ => title,                                   // For everything else
             }
-        }
+        })
+        .collect();
+
+    let theme = theme::CustomTheme {};
+    let selected_index = FuzzySelect::with_theme(&theme)
+        .with_prompt("Choose an anime:")
+        .items(&options)
+        .default(0)
+        .clear(true)
+        .interact_opt()?;
+    utils::clear();
+    if let Some(index) = selected_index {
+        Ok(anime_list[index].media.id)
+    } else {
+        Err(anyhow::anyhow!("No selection was made"))
     }
 }
 
@@ -273,22 +234,33 @@ pub struct AnimeData {
     pub progress: u32,
     pub episodes: u32,
     pub title: String,
+    pub rewatching: bool,
 }
 impl AnimeData {
-    fn new(id: i32, progress: u32, episodes: u32, title: String) -> Self {
+    fn new(id: i32, progress: u32, episodes: u32, title: String, rewatching: bool) -> Self {
         Self {
             id,
             progress,
             episodes,
             title,
+            rewatching,
         }
     }
 }
 pub async fn current(client: &Client) -> Result<AnimeData> {
+    select(client, &["CURRENT", "REPEATING"], "Start watching something new").await
+}
+
+// Gets list of COMPLETED, for picking something to rewatch
+pub async fn completed(client: &Client) -> Result<AnimeData> {
+    select(client, &["COMPLETED"], "You haven't completed anything yet").await
+}
+
+async fn select(client: &Client, statuses: &[&str], empty_message: &str) -> Result<AnimeData> {
     let user_id: i32 = get_id()?;
     let query_string = r#"
-        query ($userId: Int) {
-            MediaListCollection(userId: $userId, type: ANIME, status_in: [CURRENT, REPEATING]) {
+        query ($userId: Int, $status_in: [MediaListStatus]) {
+            MediaListCollection(userId: $userId, type: ANIME, status_in: $status_in) {
                 lists {
                     name
                     entries {
@@ -297,10 +269,15 @@ pub async fn current(client: &Client) -> Result<AnimeData> {
                             title {
                                 romaji
                                 english
+                                native
                             }
                             status
                             episodes
+                            coverImage {
+                                medium
+                            }
                         }
+                        status
                         progress
                     }
                 }
@@ -308,109 +285,332 @@ pub async fn current(client: &Client) -> Result<AnimeData> {
         }
     "#;
 
-    let variables = serde_json::json!({"userId": user_id });
-    let response = client
-        .post(ANILIST_API_URL)
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", get_token()))
-        .json(&json!({
-            "query": query_string,
-            "variables": variables
-        }))
-        .send()
-        .await;
-
-    match response {
-        Ok(res) => {
-            if res.status().is_success() {
-                let data: Value = res.json().await.expect("Failed to parse response");
-
-                let lists = data["data"]["MediaListCollection"]["lists"]
-                    .as_array()
-                    .expect("Expected 'lists' to be an array");
-
-                let mut anime_list = Vec::new();
-                for list in lists {
-                    if let Some(entries) = list["entries"].as_array() {
-                        for entry in entries {
-                            anime_list.push(entry);
+    let variables = json!({"userId": user_id, "status_in": statuses });
+    let data: ListData = graphql::authed_query(client, query_string, variables)
+        .await
+        .map_err(|e| {
+            eprintln!(
+                "Check internet connection, there might be a problem. Error retrieving data: {}",
+                e
+            );
+            log::error!("Error retrieving data: {}", e);
+            e
+        })?;
+
+    let anime_list: Vec<ListEntry> = data
+        .collection
+        .lists
+        .into_iter()
+        .flat_map(|list| list.entries)
+        .collect();
+
+    if anime_list.is_empty() {
+        println!("{}", empty_message);
+        process::exit(0);
+    }
+
+    let options: Vec<String> = anime_list
+        .iter()
+        .map(|anime| {
+            let episodes = anime
+                .media
+                .episodes
+                .map(|e| e.to_string())
+                .unwrap_or("?".to_string());
+
+            format!(
+                "{} - {}|{}",
+                anime.media.title.preferred(),
+                anime.progress.unwrap_or(0),
+                episodes
+            )
+        })
+        .collect();
+    let covers: Vec<Option<String>> = anime_list
+        .iter()
+        .map(|anime| anime.media.cover_image.clone().and_then(|c| c.medium))
+        .collect();
+
+    let selected_index = cover::select(client, "Choose an anime:", &options, &covers, 0).await?;
+
+    utils::clear();
+    if let Some(index) = selected_index {
+        let anime = &anime_list[index];
+        // * Airing shows have no episode count yet, 0 means unknown
+        Ok(AnimeData::new(
+            anime.media.id,
+            anime.progress.unwrap_or(0),
+            anime.media.episodes.unwrap_or(0),
+            anime.media.title.preferred(),
+            anime.status.as_deref() == Some("REPEATING"),
+        ))
+    } else {
+        Err(anyhow::anyhow!("No selection was made"))
+    }
+}
+
+// A list entry as it is on AniList right now, used for syncing
+pub struct ListEntryState {
+    pub media_id: i32,
+    pub title: String,
+    pub progress: u32,
+    pub status: String,
+    pub updated_at: u64,
+}
+
+// Every anime on the user's list with progress, status and when it was last changed
+pub async fn list_states(client: &Client) -> Result<Vec<ListEntryState>> {
+    let user_id: i32 = get_id()?;
+    let query_string = r#"
+        query ($userId: Int) {
+            MediaListCollection(userId: $userId, type: ANIME) {
+                lists {
+                    entries {
+                        status
+                        progress
+                        updatedAt
+                        media {
+                            id
+                            title {
+                                romaji
+                                english
+                                native
+                            }
                         }
                     }
                 }
+            }
+        }
+    "#;
 
-                if anime_list.is_empty() {
-                    println!("Start watching something new");
-                    process::exit(0);
-                }
+    let variables = json!({"userId": user_id });
+    let data: ListData = graphql::authed_query(client, query_string, variables)
+        .await
+        .with_context(|| "Failed to get list entries")?;
+
+    let mut states: Vec<ListEntryState> = Vec::new();
+    for entry in data.collection.lists.into_iter().flat_map(|list| list.entries) {
+        // * Entries in custom lists show up more than once
+        if states.iter().any(|s| s.media_id == entry.media.id) {
+            continue;
+        }
+        states.push(ListEntryState {
+            media_id: entry.media.id,
+            title: entry.media.title.preferred(),
+            progress: entry.progress.unwrap_or(0),
+            status: entry.status.unwrap_or_default(),
+            updated_at: entry.updated_at.unwrap_or(0),
+        });
+    }
+    Ok(states)
+}
 
-                let options: Vec<String> = anime_list
-                    .iter()
-                    .map(|anime| {
-                        let media = &anime["media"];
-                        let title = media["title"]["english"]
-                            .as_str()
-                            .or_else(|| media["title"]["romaji"].as_str())
-                            .unwrap_or("Unknown Title")
-                            .to_string();
-
-                        let progress = anime["progress"]
-                            .as_u64()
-                            .map(|p| p.to_string())
-                            .unwrap_or("0".to_string());
-
-                        let episodes = media["episodes"]
-                            .as_u64()
-                            .map(|e| e.to_string())
-                            .unwrap_or("?".to_string());
-
-                        format!("{} - {}|{}", title, progress, episodes)
-                    })
-                    .collect();
-
-                let theme = theme::CustomTheme {};
-                let selected_index = FuzzySelect::with_theme(&theme)
-                    .with_prompt("Choose an anime:")
-                    .items(&options)
-                    .default(0)
-                    .clear(true)
-                    .interact_opt()?;
-
-                utils::clear();
-                if let Some(index) = selected_index {
-                    let id = anime_list[index]["media"]["id"]
-                        .as_i64()
-                        .expect("No ID found") as i32;
-                    let progress = anime_list[index]["progress"]
-                        .as_u64()
-                        .expect("No progress found") as u32;
-                    let episodes = anime_list[index]["media"]["episodes"]
-                        .as_u64()
-                        .expect("No episodes found") as u32;
-                    let name = anime_list[index]["media"]["title"]["english"]
-                        .as_str()
-                        .or_else(|| anime_list[index]["media"]["title"]["romaji"].as_str())
-                        .unwrap_or("Unknown Title")
-                        .to_string();
-                    Ok(AnimeData::new(id, progress, episodes, name))
-                } else {
-                    Err(anyhow::anyhow!("No selection was made"))
+// An anime on the user's list that is still airing
+pub struct AiringEntry {
+    pub media_id: i32,
+    pub title: String,
+    pub progress: u32,
+    pub next: NextAiring,
+}
+
+impl AiringEntry {
+    // Episodes that are out but not watched yet
+    pub fn unwatched(&self) -> u32 {
+        self.next.episode.saturating_sub(1).saturating_sub(self.progress)
+    }
+}
+
+// Watching and planning anime that are still airing, the soonest episode first
+pub async fn airing(client: &Client) -> Result<Vec<AiringEntry>> {
+    let user_id: i32 = get_id()?;
+    let query_string = r#"
+        query ($userId: Int) {
+            MediaListCollection(userId: $userId, type: ANIME, status_in: [CURRENT, PLANNING, REPEATING]) {
+                lists {
+                    entries {
+                        progress
+                        media {
+                            id
+                            title {
+                                romaji
+                                english
+                                native
+                            }
+                            nextAiringEpisode {
+                                episode
+                                airingAt
+                            }
+                        }
+                    }
                 }
-            } else {
-                log::error!("Failed to fetch data: {}", res.status());
-                Err(anyhow::anyhow!("Failed to fetch data: {}", res.status()))
             }
         }
-        Err(e) => {
-            if e.is_timeout() {
-                eprintln!(
-                    "Check internet connection, there might be a problem. The request to the anilist API took too long."
-                );
-                log::warn!("Internet connection error");
-            } else {
-                eprintln!("Error retrieving data: {}", e);
-                log::error!("Error retrieving data: {}", e);
+    "#;
+
+    let variables = json!({"userId": user_id });
+    let data: ListData = graphql::authed_query(client, query_string, variables)
+        .await
+        .with_context(|| "Failed to get airing anime")?;
+
+    let mut entries: Vec<AiringEntry> = Vec::new();
+    for entry in data.collection.lists.into_iter().flat_map(|list| list.entries) {
+        // * Only releasing shows have a next episode
+        let Some(next) = entry.media.next_airing_episode else {
+            continue;
+        };
+        if entries.iter().any(|e| e.media_id == entry.media.id) {
+            continue;
+        }
+        entries.push(AiringEntry {
+            media_id: entry.media.id,
+            title: entry.media.title.preferred(),
+            progress: entry.progress.unwrap_or(0),
+            next,
+        });
+    }
+    entries.sort_by_key(|e| e.next.airing_at);
+    Ok(entries)
+}
+
+// Upcoming episodes of an anime on the user's list
+pub struct Schedule {
+    pub media_id: i32,
+    pub title: String,
+    pub duration: u32, // Minutes per episode
+    pub episodes: Vec<NextAiring>,
+}
+
+#[derive(Deserialize)]
+struct ScheduleData {
+    #[serde(rename = "MediaListCollection")]
+    collection: ScheduleCollection,
+}
+
+#[derive(Deserialize)]
+struct ScheduleCollection {
+    lists: Vec<ScheduleList>,
+}
+
+#[derive(Deserialize)]
+struct ScheduleList {
+    #[serde(default)]
+    entries: Vec<ScheduleEntry>,
+}
+
+#[derive(Deserialize)]
+struct ScheduleEntry {
+    media: ScheduleMedia,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScheduleMedia {
+    id: i32,
+    title: Title,
+    duration: Option<u32>,
+    airing_schedule: AiringSchedule,
+}
+
+#[derive(Deserialize)]
+struct AiringSchedule {
+    nodes: Vec<NextAiring>,
+}
+
+// Every episode that hasn't aired yet, for watching and planning anime
+pub async fn schedules(client: &Client) -> Result<Vec<Schedule>> {
+    let user_id: i32 = get_id()?;
+    let query_string = r#"
+        query ($userId: Int) {
+            MediaListCollection(userId: $userId, type: ANIME, status_in: [CURRENT, PLANNING, REPEATING]) {
+                lists {
+                    entries {
+                        media {
+                            id
+                            title {
+                                romaji
+                                english
+                                native
+                            }
+                            duration
+                            airingSchedule(notYetAired: true, perPage: 25) {
+                                nodes {
+                                    episode
+                                    airingAt
+                                }
+                            }
+                        }
+                    }
+                }
             }
-            Err(e.into())
         }
+    "#;
+
+    let variables = json!({"userId": user_id });
+    let data: ScheduleData = graphql::authed_query(client, query_string, variables)
+        .await
+        .with_context(|| "Failed to get airing schedules")?;
+
+    let mut schedules: Vec<Schedule> = Vec::new();
+    for entry in data.collection.lists.into_iter().flat_map(|list| list.entries) {
+        let media = entry.media;
+        if media.airing_schedule.nodes.is_empty() || schedules.iter().any(|s| s.media_id == media.id) {
+            continue;
+        }
+        schedules.push(Schedule {
+            media_id: media.id,
+            title: media.title.preferred(),
+            duration: media.duration.unwrap_or(24),
+            episodes: media.airing_schedule.nodes,
+        });
     }
+    Ok(schedules)
+}
+
+// Completed anime the user scored the highest, best first
+// Returns media id and title
+pub async fn top_rated(client: &Client, limit: usize) -> Result<Vec<(i32, String)>> {
+    let user_id: i32 = get_id()?;
+    let query_string = r#"
+        query ($userId: Int) {
+            MediaListCollection(userId: $userId, type: ANIME, status: COMPLETED, sort: SCORE_DESC) {
+                lists {
+                    entries {
+                        score(format: POINT_100)
+                        media {
+                            id
+                            title {
+                                romaji
+                                english
+                                native
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    "#;
+
+    let variables = json!({"userId": user_id });
+    let data: ListData = graphql::authed_query(client, query_string, variables)
+        .await
+        .with_context(|| "Failed to get completed anime")?;
+
+    let mut entries: Vec<ListEntry> = data
+        .collection
+        .lists
+        .into_iter()
+        .flat_map(|list| list.entries)
+        .filter(|entry| entry.score.unwrap_or(0.0) > 0.0)
+        .collect();
+    // * Custom lists come as separate lists, so the order has to be redone
+    entries.sort_by(|a, b| b.score.unwrap_or(0.0).total_cmp(&a.score.unwrap_or(0.0)));
+    // * An anime on custom lists shows up more than once, not always next to itself
+    let mut seen = HashSet::new();
+    entries.retain(|entry| seen.insert(entry.media.id));
+
+    Ok(entries
+        .into_iter()
+        .take(limit)
+        .map(|entry| (entry.media.id, entry.media.title.preferred()))
+        .collect())
 }
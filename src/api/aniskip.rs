@@ -1,4 +1,6 @@
 // =============== Imports ================
+use crate::http::{self, Service};
+
 use anyhow::{Context, Result};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -66,9 +68,7 @@ pub async fn get_ani_skip_data(client: &Client, anime_mal_id: i32, episode: u32)
         base_url, anime_mal_id, episode
     );
 
-    let response = client
-        .get(&url)
-        .send()
+    let response = http::send(Service::AniSkip, client.get(&url))
         .await
         .with_context(|| format!("Failed to send request to AniSkip API: {}", url))?;
 
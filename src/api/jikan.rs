@@ -1,51 +1,186 @@
+// Getting episode data (filler, recap, titles) from Jikan
+
 // =============== Imports ================
+use crate::http::{self, Service};
+use crate::utils;
+
+use anyhow::{Context, Result};
 use reqwest::{self, Client};
+use serde::{Deserialize, Serialize};
 use serde_json;
-use serde::Deserialize;
-use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::{self, create_dir_all};
+use std::path::PathBuf;
+use tokio::sync::Mutex;
+
+// Constant variables
+const JIKAN_API_URL: &str = "https://api.jikan.moe/v4/anime";
+// Cached episode lists are refetched after a day, airing shows get new episodes
+const CACHE_TTL: u64 = 60 * 60 * 24;
+
+// * Only one season is fetched at a time, so prefetching tasks wait for the cache instead of refetching
+static FETCH_LOCK: Mutex<()> = Mutex::const_new(());
 
 #[derive(Deserialize)]
-struct EpisodeWrapper {
-    data: JikanData,
+struct EpisodesPage {
+    data: Vec<JikanEpisode>,
+    pagination: Pagination,
 }
+
+#[derive(Deserialize)]
+struct Pagination {
+    has_next_page: bool,
+}
+
 #[derive(Deserialize)]
-struct JikanData {
+struct JikanEpisode {
+    mal_id: u32, // This is the episode number
+    title: Option<String>,
+    aired: Option<String>,
+    #[serde(default)]
     filler: bool,
+    #[serde(default)]
+    recap: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EpisodeInfo {
+    pub title: Option<String>,
+    pub aired: Option<String>,
+    pub filler: bool,
+    pub recap: bool,
 }
 
-pub async fn filler(client: &Client, mal_id: i32, episode: u32) -> Result<bool> {
-    let base_url = "https://api.jikan.moe/v4/anime";
-    let url = format!("{}/{}/episodes/{}", base_url, mal_id, episode);
-
-    let response = client.get(url.clone()).send().await
-        .with_context(|| format!("Failed to send request to Jikan API: {}", url));
-
-    match response {
-        Ok(res) => {
-            if res.status().is_success() {
-                match res.text().await {
-                    Ok(res) => {
-                        let json = res;
-                        let data: EpisodeWrapper = serde_json::from_str(&json)
-                            .with_context(|| "Failed to parse JSON response from Jikan API")?;
-                        Ok(data.data.filler)
-                    }
-                    Err(err) => {
-                        eprintln!("Failed to read response body: {}", err);
-                        Ok(false)
-                    },
-                }
+// Every episode of a season, keyed by episode number
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct EpisodeMap {
+    pub fetched_at: u64,
+    pub episodes: HashMap<u32, EpisodeInfo>,
+}
+
+impl EpisodeMap {
+    pub fn get(&self, episode: u32) -> Option<&EpisodeInfo> {
+        self.episodes.get(&episode)
+    }
+
+    pub fn is_filler(&self, episode: u32) -> bool {
+        self.get(episode).is_some_and(|e| e.filler)
+    }
+
+    pub fn is_recap(&self, episode: u32) -> bool {
+        self.get(episode).is_some_and(|e| e.recap)
+    }
+
+    fn is_fresh(&self) -> bool {
+        utils::now().saturating_sub(self.fetched_at) < CACHE_TTL
+    }
+}
+
+pub fn cache_path() -> Result<PathBuf> {
+    let mut path = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("yato");
+    create_dir_all(&path)?;
+    path.push("jikan_cache.json");
+    Ok(path)
+}
+
+fn load_cache() -> HashMap<i32, EpisodeMap> {
+    let Ok(path) = cache_path() else {
+        return HashMap::new();
+    };
+    match fs::read_to_string(&path) {
+        Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+            log::warn!("Failed to parse Jikan cache, starting a new one: {}", e);
+            HashMap::new()
+        }),
+        Err(_) => HashMap::new(),
+    }
+}
+
+fn save_cache(cache: &HashMap<i32, EpisodeMap>) -> Result<()> {
+    let path = cache_path()?;
+    let json = serde_json::to_string(cache).with_context(|| "Failed to serialize Jikan cache")?;
+    fs::write(&path, json).with_context(|| format!("Failed to write Jikan cache: {:?}", path))?;
+    Ok(())
+}
+
+// Returns the episode list of the anime, from the cache if it's fresh enough
+// Falls back to a stale cache entry if Jikan can't be reached
+pub async fn episodes(client: &Client, mal_id: i32) -> Result<EpisodeMap> {
+    let _lock = FETCH_LOCK.lock().await;
+
+    let mut cache = load_cache();
+    if let Some(map) = cache.get(&mal_id).filter(|map| map.is_fresh()) {
+        return Ok(map.clone());
+    }
+
+    log::info!("Fetching episode list from Jikan for MAL id: {}", mal_id);
+    match fetch_all_pages(client, mal_id).await {
+        Ok(map) => {
+            cache.insert(mal_id, map.clone());
+            if let Err(e) = save_cache(&cache) {
+                log::warn!("{}", e);
             }
-            else {
-                log::error!("Error getting episode data from Jikan. Status: {}", res.status());
-                eprintln!("Couldn't get filler data.");
-                Ok(false)
+            Ok(map)
+        }
+        Err(e) => match cache.remove(&mal_id) {
+            Some(stale) => {
+                log::warn!("Using stale Jikan cache for MAL id {}: {}", mal_id, e);
+                Ok(stale)
             }
+            None => Err(e),
+        },
+    }
+}
+
+async fn fetch_all_pages(client: &Client, mal_id: i32) -> Result<EpisodeMap> {
+    let mut map = EpisodeMap {
+        fetched_at: utils::now(),
+        episodes: HashMap::new(),
+    };
+
+    let mut page = 1;
+    loop {
+        let url = format!("{}/{}/episodes?page={}", JIKAN_API_URL, mal_id, page);
+        let data = fetch_page(client, &url).await?;
+
+        for episode in data.data {
+            map.episodes.insert(
+                episode.mal_id,
+                EpisodeInfo {
+                    title: episode.title,
+                    aired: episode.aired,
+                    filler: episode.filler,
+                    recap: episode.recap,
+                },
+            );
         }
-        Err(e) => {
-            log::error!("Error fetching data from Jikan: {}", e);
-            eprintln!("Error fetching data from Jikan: {}", e);
-            Ok(false)
+
+        if !data.pagination.has_next_page {
+            break;
         }
+        page += 1;
     }
-}
\ No newline at end of file
+
+    log::info!(
+        "Got {} episodes from Jikan for MAL id: {}",
+        map.episodes.len(),
+        mal_id
+    );
+    Ok(map)
+}
+
+async fn fetch_page(client: &Client, url: &str) -> Result<EpisodesPage> {
+    let res = http::send(Service::Jikan, client.get(url))
+        .await
+        .with_context(|| format!("Failed to send request to Jikan API: {}", url))?;
+
+    if !res.status().is_success() {
+        log::error!("Error getting episode data from Jikan. Status: {}", res.status());
+        return Err(anyhow::anyhow!("Jikan returned status: {}", res.status()));
+    }
+
+    res.json::<EpisodesPage>()
+        .await
+        .with_context(|| "Failed to parse JSON response from Jikan API")
+}
@@ -2,6 +2,7 @@
 use crate::api::anilist;
 use crate::config::{self, Config};
 use crate::discord_rpc::{self, is_discord_running};
+use crate::profile;
 use crate::utils;
 
 use anyhow::{Context, Result};
@@ -20,7 +21,7 @@ fn build() -> Command {
                 .help("Edit your config file")
                 .long_help("Edit your config file in nano")
                 .action(ArgAction::SetTrue)
-                .conflicts_with_all(vec!["information", "anime", "number", "dub", "sub", "language", "quality", "rpc", "change-token", "new", "skip-op", "skip-ed", "skip-recap", "skip-filler"])
+                .conflicts_with_all(vec!["information", "anime", "number", "dub", "sub", "language", "quality", "rpc", "change-token", "login", "new", "skip-op", "skip-ed", "skip-recap", "skip-filler", "filler", "skip-recap-episodes"])
                 .required(false),
         )
         .arg(
@@ -90,6 +91,15 @@ fn build() -> Command {
                 .conflicts_with_all(&["edit", "anime", "number"])
                 .required(false),
         )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .help("Print the information as JSON")
+                .long_help("Print the information as JSON, the way AniList sends it")
+                .action(ArgAction::SetTrue)
+                .requires("information")
+                .required(false),
+        )
         .arg(
             Arg::new("number")
                 .short('n')
@@ -122,6 +132,15 @@ fn build() -> Command {
                 .conflicts_with("information")
                 .required(false),
         )
+        .arg(
+            Arg::new("login")
+                .long("login")
+                .help("Log in to AniList through your browser")
+                .long_help("Log in to AniList through your browser, replacing the stored token")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(vec!["edit", "information", "change-token"])
+                .required(false),
+        )
         .arg(
             Arg::new("change-token")
                 .long("change-token")
@@ -179,6 +198,15 @@ fn build() -> Command {
                 .action(ArgAction::SetTrue)
                 .required(false),
         )
+        .arg(
+            Arg::new("filler")
+                .long("filler")
+                .value_name("POLICY")
+                .help("What to do with filler episodes")
+                .value_parser(["skip", "ask", "mark", "play"])
+                .conflicts_with_all(vec!["edit", "information", "skip-filler"])
+                .required(false),
+        )
         .arg(
             Arg::new("skip-recap")
                 .long("skip-recap")
@@ -187,16 +215,72 @@ fn build() -> Command {
                 .action(ArgAction::SetTrue)
                 .required(false),
         )
+        .arg(
+            Arg::new("skip-recap-episodes")
+                .long("skip-recap-episodes")
+                .help("Toggles the setting set in the config")
+                .conflicts_with_all(vec!["edit", "information"])
+                .action(ArgAction::SetTrue)
+                .required(false),
+        )
+        .arg(
+            Arg::new("outbox")
+                .long("outbox")
+                .help("Show AniList updates waiting to be sent")
+                .long_help("Show AniList updates waiting to be sent, retry or remove them")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(vec!["edit", "information", "anime", "number", "new"])
+                .required(false),
+        )
+        .arg(
+            Arg::new("sync")
+                .long("sync")
+                .help("Sync local progress with AniList")
+                .long_help("Sync local progress with AniList, whichever was changed last wins")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(vec!["edit", "information", "anime", "number", "new", "outbox"])
+                .required(false),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .help("Only show what --sync would change")
+                .action(ArgAction::SetTrue)
+                .requires("sync")
+                .required(false),
+        )
+        .arg(
+            Arg::new("ics")
+                .long("ics")
+                .value_name("FILE")
+                .help("Export upcoming episodes as an iCalendar file")
+                .long_help("Export upcoming episodes of watching and planning anime as an iCalendar file, importing it again updates the events")
+                .conflicts_with_all(vec!["edit", "information", "anime", "number", "new", "outbox", "sync"])
+                .required(false),
+        )
+        .arg(
+            Arg::new("profile")
+                .short('p')
+                .long("profile")
+                .value_name("NAME")
+                .help("Use (or create) the given profile")
+                .long_help("Use (or create) the given profile, each profile has its own AniList account, progress and overrides")
+                .required(false),
+        )
+}
+
+// * Parsed before anything else, the profile has to be known before the config is loaded
+pub fn parse() -> ArgMatches {
+    build().get_matches()
 }
 
 pub async fn handle_args(
+    matches: ArgMatches,
     config: &mut Config,
     client: &Client,
 ) -> Result<(ArgMatches, discord_rpc_client::Client)> {
-    let matches = build().get_matches();
-
     if matches.get_flag("edit") {
-        let config_file = dirs::config_dir().unwrap().join("yato/yato.conf");
+        let config_file = profile::config_file();
         if !config_file.exists() {
             // Create the config file if it doesn't exist
             config::create(&config_file);
@@ -239,7 +323,15 @@ pub async fn handle_args(
     }
 
     if matches.get_flag("change-token") {
-        anilist::user_fetch::remove_token_file()?;
+        anilist::auth::logout()?;
+    }
+
+    if matches.get_flag("login") {
+        anilist::auth::logout()?;
+        anilist::auth::login().await?;
+        anilist::user_fetch::check_credentials(client).await?;
+        println!("Successfully logged in to AniList!");
+        process::exit(0);
     }
 
     if matches.get_flag("dub") {
@@ -275,8 +367,14 @@ pub async fn handle_args(
         config.skip_recap = !default_skip;
     }
     if matches.get_flag("skip-filler") {
-        let default_skip = config.skip_filler;
-        config.skip_filler = !default_skip;
+        config.filler_policy = config.filler_policy.toggled();
+    }
+    if let Some(policy) = matches.get_one::<String>("filler") {
+        config.filler_policy = policy.parse()?;
+    }
+    if matches.get_flag("skip-recap-episodes") {
+        let default_skip = config.skip_recap_episodes;
+        config.skip_recap_episodes = !default_skip;
     }
 
     let mut rpc_client = discord_rpc::init();
@@ -296,12 +394,15 @@ pub async fn handle_args(
         .map(String::as_str)
         .unwrap_or_else(|| "");
 
-    utils::clear();
+    let as_json = matches.get_flag("json");
+    if !as_json {
+        utils::clear();
+    }
     if !original_input.is_empty() {
         let input = original_input.parse();
         match input {
             Ok(i) => {
-                anilist::fetch::information(&client, i)
+                anilist::fetch::information(client, i, as_json)
                     .await
                     .with_context(|| format!("Failed to fetch information for ID: {}", i))?;
             }
@@ -309,7 +410,7 @@ pub async fn handle_args(
                 let i = anilist::fetch::search(&client, original_input.to_string()).await;
                 match i {
                     Ok(i) => {
-                        anilist::fetch::information(&client, i)
+                        anilist::fetch::information(client, i, as_json)
                             .await
                             .with_context(|| {
                                 format!("Failed to fetch information for ID: {}", i)
@@ -1,13 +1,113 @@
 // =============== Imports ================
+use crate::profile;
+
 use std::path::PathBuf;
-use std::fs;
+use std::{fmt, fs};
+use std::str::FromStr;
 use serde_yaml;
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer, Serialize};
 use log;
 use anyhow::{Context, Result};
 
+// What to do when the next episode is a filler
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FillerPolicy {
+    Skip,
+    Ask,
+    #[serde(rename = "mark")]
+    MarkWatched, // Skip, but still count them as watched on AniList
+    Play,
+}
+
+impl FillerPolicy {
+    pub const ALL: [FillerPolicy; 4] = [
+        FillerPolicy::Skip,
+        FillerPolicy::Ask,
+        FillerPolicy::MarkWatched,
+        FillerPolicy::Play,
+    ];
+
+    // Used by --skip-filler and the old boolean overrides
+    pub fn toggled(self) -> Self {
+        match self {
+            FillerPolicy::Play => FillerPolicy::Skip,
+            _ => FillerPolicy::Play,
+        }
+    }
+
+    pub fn skips(self) -> bool {
+        self != FillerPolicy::Play
+    }
+}
+
+impl fmt::Display for FillerPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            FillerPolicy::Skip => "skip",
+            FillerPolicy::Ask => "ask before skipping",
+            FillerPolicy::MarkWatched => "skip and mark as watched",
+            FillerPolicy::Play => "play",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+impl FromStr for FillerPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "skip" => Ok(FillerPolicy::Skip),
+            "ask" => Ok(FillerPolicy::Ask),
+            "mark" => Ok(FillerPolicy::MarkWatched),
+            "play" => Ok(FillerPolicy::Play),
+            _ => Err(anyhow::anyhow!("Unknown filler policy: {}", s)),
+        }
+    }
+}
+
+// How cover images are drawn in the terminal
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CoverArt {
+    Auto, // Kitty or sixel if the terminal supports it, colored blocks otherwise
+    Kitty,
+    Sixel,
+    Blocks,
+    Off,
+}
+
+// Which title of an anime is shown
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TitleLanguage {
+    Romaji,
+    English,
+    Native,
+    Anilist, // The one chosen in the AniList settings of the user
+}
 
+// Old config files still have `skip_filler: true/false`
+fn filler_policy_or_bool<'de, D: Deserializer<'de>>(deserializer: D) -> Result<FillerPolicy, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Setting {
+        Old(bool),
+        New(FillerPolicy),
+    }
+
+    Ok(match Setting::deserialize(deserializer)? {
+        Setting::Old(true) => FillerPolicy::Skip,
+        Setting::Old(false) => FillerPolicy::Play,
+        Setting::New(policy) => policy,
+    })
+}
+
+
+// Settings missing from the config file fall back to their default values
 #[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct Config {
     pub player: String,
     pub player_args: String,
@@ -19,7 +119,16 @@ pub struct Config {
     pub skip_opening: bool,
     pub skip_credits: bool,
     pub skip_recap: bool,
-    pub skip_filler: bool,
+    #[serde(alias = "skip_filler", deserialize_with = "filler_policy_or_bool")]
+    pub filler_policy: FillerPolicy,
+    pub skip_recap_episodes: bool,
+    pub auto_status_watching: bool,
+    pub auto_status_completed: bool,
+    pub auto_status_rewatch_completed: bool,
+    pub cover_art: CoverArt,
+    pub title_language: TitleLanguage,
+    pub anilist_client_id: String,
+    pub login_redirect_port: u16, // 0 means the token is pasted in
     pub quality: String,
     pub language: String,
 }
@@ -38,7 +147,15 @@ impl Default for Config {
             skip_opening: true,
             skip_credits: true,
             skip_recap: true,
-            skip_filler: true,
+            filler_policy: FillerPolicy::Skip,
+            skip_recap_episodes: false,
+            auto_status_watching: true,
+            auto_status_completed: true,
+            auto_status_rewatch_completed: true,
+            cover_art: CoverArt::Auto,
+            title_language: TitleLanguage::English,
+            anilist_client_id: "25501".to_string(),
+            login_redirect_port: 0,
             quality: "best".to_string(),
             language: "english".to_string(),
         }
@@ -62,7 +179,28 @@ completion_time: 85
 skip_opening: true
 skip_credits: true
 skip_recap: true
-skip_filler: true
+filler_policy: "skip"
+# What to do with filler episodes: "skip", "ask" (before every filler run), "mark" (skip, but mark them as watched on Anilist) or "play"
+# With "skip" and "ask" the skipped episodes only count as watched once the episode after them is synced
+skip_recap_episodes: false
+# Skips whole recap episodes, not just the recap part at the start of an episode
+
+auto_status_watching: true
+# Planning and paused anime are set to watching when an episode is synced
+auto_status_completed: true
+# Watching anime are set to completed after the last episode
+auto_status_rewatch_completed: true
+# Rewatched anime are set to completed after the last episode, and the rewatch count goes up by one
+
+cover_art: "auto"
+# Cover images in the info page and lists: "auto", "kitty", "sixel", "blocks" (works in most terminals) or "off"
+title_language: "english"
+# Titles shown everywhere: "romaji", "english", "native" or "anilist" (the one set in your AniList settings)
+
+anilist_client_id: "25501"
+login_redirect_port: 0
+# Logging in pastes the token by default. To skip that, register your own client on AniList with
+# http://localhost:<port>/callback as the redirect URL and put its id and port here
 
 quality: "best"
 # You can change this to any other quality. If desired quality is not available, the app will choose the best available quality.
@@ -83,15 +221,13 @@ discord_presence: false "#;
 // If the config file doesn't exist, returns the default config
 pub fn load_config() -> Config {
     log::info!("Loading configuration");
-    let config_dir = dirs::config_dir().unwrap_or_else(|| {
-        log::error!("Couldn't find config directory!");
-        panic!("Could not find config directory. Please set the XDG_CONFIG_HOME environment variable.");
-    });
-    let config_path = config_dir.join("yato");
-    if !config_path.exists() {
-        std::fs::create_dir_all(&config_path).expect("Failed to create config directory");
-    }
-    let config_file = config_path.join("yato.conf");
+    // * Profiles can have their own config, otherwise the shared one is used
+    let config_file = profile::config_file();
+    if let Some(config_path) = config_file.parent()
+        && !config_path.exists()
+    {
+        std::fs::create_dir_all(config_path).expect("Failed to create config directory");
+    }
     if let Ok(contents) = fs::read_to_string(&config_file) {
         let config: Config = serde_yaml::from_str(&contents)
             .with_context(|| "Failed to parse config file")
@@ -118,3 +254,54 @@ pub fn test(config: &Config) -> Result<()> {
     log::info!("Configuration test passed");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(yaml: &str) -> Result<FillerPolicy, serde_yaml::Error> {
+        serde_yaml::from_str::<Config>(yaml).map(|config| config.filler_policy)
+    }
+
+    #[test]
+    fn reads_the_old_skip_filler_setting() {
+        assert_eq!(policy("skip_filler: true").unwrap(), FillerPolicy::Skip);
+        assert_eq!(policy("skip_filler: false").unwrap(), FillerPolicy::Play);
+    }
+
+    #[test]
+    fn reads_filler_policies() {
+        assert_eq!(policy("filler_policy: skip").unwrap(), FillerPolicy::Skip);
+        assert_eq!(policy("filler_policy: ask").unwrap(), FillerPolicy::Ask);
+        assert_eq!(policy("filler_policy: mark").unwrap(), FillerPolicy::MarkWatched);
+        assert_eq!(policy("filler_policy: play").unwrap(), FillerPolicy::Play);
+        assert_eq!(policy("filler_policy: true").unwrap(), FillerPolicy::Skip);
+    }
+
+    #[test]
+    fn rejects_unknown_policies() {
+        assert!(policy("filler_policy: sometimes").is_err());
+        assert!("sometimes".parse::<FillerPolicy>().is_err());
+    }
+
+    #[test]
+    fn missing_policy_uses_the_default() {
+        assert_eq!(policy("player: mpv").unwrap(), Config::default().filler_policy);
+    }
+
+    #[test]
+    fn policies_parse_back_from_their_config_names() {
+        for policy in FillerPolicy::ALL {
+            let name = serde_yaml::to_string(&policy).unwrap();
+            assert_eq!(name.trim().parse::<FillerPolicy>().unwrap(), policy);
+        }
+    }
+
+    #[test]
+    fn toggling_switches_between_skipping_and_playing() {
+        assert_eq!(FillerPolicy::Play.toggled(), FillerPolicy::Skip);
+        assert_eq!(FillerPolicy::MarkWatched.toggled(), FillerPolicy::Play);
+        assert!(!FillerPolicy::Play.skips());
+        assert!(FillerPolicy::Ask.skips());
+    }
+}
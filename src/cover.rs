@@ -0,0 +1,500 @@
+// Cover images in the terminal
+// Kitty graphics or sixel where the terminal supports them, colored half blocks everywhere else
+
+// =============== Imports ================
+use crate::config::{self, CoverArt};
+use crate::http::{self, Service};
+use crate::theme;
+
+use anyhow::Result;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use console::{Key, Term, style};
+use dialoguer::FuzzySelect;
+use futures::stream::{self, StreamExt};
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use reqwest::Client;
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::{Arc, LazyLock, Mutex};
+
+// * Most terminal fonts are about this big, good enough for sizing the pictures
+const CELL_WIDTH: u32 = 10;
+const CELL_HEIGHT: u32 = 20;
+// Size of the cover next to lists
+const LIST_COVER_COLUMNS: usize = 24;
+const LIST_COVER_ROWS: usize = 17;
+// Size of the cover on the info page
+const INFO_COVER_ROWS: usize = 20;
+// Covers downloaded at the same time while a list is shown
+const PARALLEL_DOWNLOADS: usize = 8;
+
+// * Covers are downloaded once per run
+static CACHE: LazyLock<Mutex<HashMap<String, Arc<DynamicImage>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protocol {
+    Kitty,
+    Sixel,
+    Blocks,
+}
+
+// The way covers can be drawn right now, None if they can't (or shouldn't) be
+pub fn protocol() -> Option<Protocol> {
+    if !Term::stderr().is_term() {
+        return None;
+    }
+    match config::load_config().cover_art {
+        CoverArt::Off => None,
+        CoverArt::Kitty => Some(Protocol::Kitty),
+        CoverArt::Sixel => Some(Protocol::Sixel),
+        CoverArt::Blocks => Some(Protocol::Blocks),
+        CoverArt::Auto => detect(),
+    }
+}
+
+fn detect() -> Option<Protocol> {
+    let term = std::env::var("TERM").unwrap_or_default();
+    let program = std::env::var("TERM_PROGRAM").unwrap_or_default();
+    let color = std::env::var("COLORTERM").unwrap_or_default();
+
+    if std::env::var_os("KITTY_WINDOW_ID").is_some()
+        || term.contains("kitty")
+        || term.contains("ghostty")
+        || matches!(program.as_str(), "WezTerm" | "ghostty")
+    {
+        Some(Protocol::Kitty)
+    } else if term.contains("sixel") || term.starts_with("foot") || term.starts_with("mlterm") {
+        Some(Protocol::Sixel)
+    } else if color == "truecolor" || color == "24bit" {
+        Some(Protocol::Blocks)
+    } else {
+        log::info!(
+            "No cover art, terminal {} doesn't look like it supports it",
+            term
+        );
+        None
+    }
+}
+
+// Downloads and decodes the cover, None if it couldn't be
+pub async fn download(client: &Client, url: &str) -> Option<Arc<DynamicImage>> {
+    if let Some(image) = CACHE.lock().unwrap().get(url) {
+        return Some(image.clone());
+    }
+
+    let bytes = match http::send(Service::Other, client.get(url)).await {
+        Ok(response) => response.bytes().await.ok()?,
+        Err(e) => {
+            log::warn!("Failed to download cover {}: {:#}", url, e);
+            return None;
+        }
+    };
+    let image = match image::load_from_memory(&bytes) {
+        Ok(image) => Arc::new(image),
+        Err(e) => {
+            log::warn!("Failed to decode cover {}: {}", url, e);
+            return None;
+        }
+    };
+    CACHE.lock().unwrap().insert(url.to_string(), image.clone());
+    Some(image)
+}
+
+// =============== Rendering ===============
+// The picture as lines of text, or one escape sequence for kitty and sixel
+// Each line has to be printed at the same column, the cursor isn't moved by kitty images
+fn render(image: &DynamicImage, protocol: Protocol, columns: usize, rows: usize) -> Vec<String> {
+    match protocol {
+        Protocol::Blocks => {
+            let resized = image
+                .resize(columns as u32, rows as u32 * 2, FilterType::Triangle)
+                .to_rgba8();
+            blocks(&resized)
+        }
+        Protocol::Kitty => {
+            let resized = image.resize(
+                columns as u32 * CELL_WIDTH,
+                rows as u32 * CELL_HEIGHT,
+                FilterType::Triangle,
+            );
+            vec![kitty(&resized)]
+        }
+        Protocol::Sixel => {
+            let resized = image
+                .resize(
+                    columns as u32 * CELL_WIDTH,
+                    rows as u32 * CELL_HEIGHT,
+                    FilterType::Triangle,
+                )
+                .to_rgba8();
+            vec![sixel(&resized)]
+        }
+    }
+}
+
+// Two pixels per cell, the upper one is the text color and the lower one the background
+fn blocks(image: &RgbaImage) -> Vec<String> {
+    let (width, height) = image.dimensions();
+    (0..height)
+        .step_by(2)
+        .map(|y| {
+            let mut line = String::new();
+            for x in 0..width {
+                let top = image.get_pixel(x, y);
+                let bottom = if y + 1 < height {
+                    image.get_pixel(x, y + 1)
+                } else {
+                    top
+                };
+                line.push_str(&format!(
+                    "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m▀",
+                    top[0], top[1], top[2], bottom[0], bottom[1], bottom[2]
+                ));
+            }
+            line.push_str("\x1b[0m");
+            line
+        })
+        .collect()
+}
+
+// Sent as PNG, kitty scales it into the given cells
+fn kitty(image: &DynamicImage) -> String {
+    let mut png = Vec::new();
+    if let Err(e) = image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png) {
+        log::warn!("Failed to encode cover: {}", e);
+        return String::new();
+    }
+    let columns = image.width().div_ceil(CELL_WIDTH);
+    let rows = image.height().div_ceil(CELL_HEIGHT);
+    let encoded = STANDARD.encode(&png);
+
+    // * Kitty only takes 4096 bytes per escape sequence
+    let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(4096).collect();
+    let mut out = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+        if i == 0 {
+            out.push_str(&format!(
+                "\x1b_Gf=100,a=T,q=2,C=1,c={},r={},m={};{}\x1b\\",
+                columns, rows, more, chunk
+            ));
+        } else {
+            out.push_str(&format!("\x1b_Gm={};{}\x1b\\", more, chunk));
+        }
+    }
+    out
+}
+
+// Removes every kitty picture from the screen, clearing the screen doesn't do that
+fn kitty_clear() -> &'static str {
+    "\x1b_Ga=d,d=A,q=2\x1b\\"
+}
+
+// Sixel with a 6x6x6 color cube, plenty for a small cover
+fn sixel(image: &RgbaImage) -> String {
+    let (width, height) = image.dimensions();
+    let level = |c: u8| (c as usize * 5 + 127) / 255;
+    let colors: Vec<Option<usize>> = image
+        .pixels()
+        .map(|p| (p[3] > 127).then(|| level(p[0]) * 36 + level(p[1]) * 6 + level(p[2])))
+        .collect();
+
+    let mut out = format!("\x1bP0;1;0q\"1;1;{};{}", width, height);
+    for i in 0..216 {
+        out.push_str(&format!(
+            "#{};2;{};{};{}",
+            i,
+            i / 36 * 20,
+            i / 6 % 6 * 20,
+            i % 6 * 20
+        ));
+    }
+
+    for band in (0..height).step_by(6) {
+        let rows = band..(band + 6).min(height);
+        let colors = &colors;
+        let mut used: Vec<usize> = rows
+            .clone()
+            .flat_map(|y| (0..width).filter_map(move |x| colors[(y * width + x) as usize]))
+            .collect();
+        used.sort_unstable();
+        used.dedup();
+
+        for (n, color) in used.iter().enumerate() {
+            if n > 0 {
+                out.push('$'); // Back to the start of the band for the next color
+            }
+            out.push_str(&format!("#{}", color));
+            let mut run: Option<(char, usize)> = None;
+            for x in 0..width {
+                let bits = rows
+                    .clone()
+                    .filter(|y| colors[(y * width + x) as usize] == Some(*color))
+                    .fold(0u8, |bits, y| bits | 1 << (y - band));
+                let c = char::from(63 + bits);
+                run = match run {
+                    Some((last, count)) if last == c => Some((last, count + 1)),
+                    Some(previous) => {
+                        push_run(&mut out, previous);
+                        Some((c, 1))
+                    }
+                    None => Some((c, 1)),
+                };
+            }
+            if let Some(last) = run {
+                push_run(&mut out, last);
+            }
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+    out
+}
+
+fn push_run(out: &mut String, (c, count): (char, usize)) {
+    if count > 3 {
+        out.push_str(&format!("!{}{}", count, c));
+    } else {
+        out.extend(std::iter::repeat_n(c, count));
+    }
+}
+
+// =============== Showing ===============
+// Prints the cover where the cursor is, for the info page
+pub async fn print(client: &Client, url: &str) {
+    // * This one goes to stdout, unlike the lists
+    if !Term::stdout().is_term() {
+        return;
+    }
+    let Some(protocol) = protocol() else {
+        return;
+    };
+    let Some(image) = download(client, url).await else {
+        return;
+    };
+    let columns = INFO_COVER_ROWS * 3 / 2;
+    let lines = render(&image, protocol, columns, INFO_COVER_ROWS);
+    match protocol {
+        Protocol::Blocks => {
+            for line in lines {
+                println!("{}", line);
+            }
+        }
+        Protocol::Kitty => {
+            // * The cursor stays where it was, so the rows are skipped by hand
+            print!("{}", lines.concat());
+            println!("{}", "\n".repeat(INFO_COVER_ROWS));
+        }
+        Protocol::Sixel => println!("{}", lines.concat()),
+    }
+}
+
+// Fuzzy select with the cover of the highlighted entry next to the list
+// Falls back to the usual select if covers can't be shown
+pub async fn select(
+    client: &Client,
+    prompt: &str,
+    items: &[String],
+    covers: &[Option<String>],
+    default: usize,
+) -> Result<Option<usize>> {
+    let term = Term::stderr();
+    let (_, width) = term.size();
+    let protocol = protocol().filter(|_| width as usize >= LIST_COVER_COLUMNS + 40);
+    let Some(protocol) = protocol else {
+        let theme = theme::CustomTheme {};
+        return Ok(FuzzySelect::with_theme(&theme)
+            .with_prompt(prompt)
+            .items(items)
+            .default(default)
+            .interact_opt()?);
+    };
+
+    let picker = Picker {
+        client,
+        term,
+        protocol,
+        prompt,
+        items,
+        covers,
+        images: HashMap::new(),
+        rendered: HashMap::new(),
+    };
+    picker.run(default).await
+}
+
+// Takes the covers off the screen and shows the cursor again once the picker is done
+struct Restore {
+    term: Term,
+    protocol: Protocol,
+}
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        if let Err(e) = clear(&self.term, self.protocol) {
+            log::warn!("Failed to clear the picker: {}", e);
+        }
+        if let Err(e) = self.term.show_cursor() {
+            log::warn!("Failed to show the cursor: {}", e);
+        }
+    }
+}
+
+fn clear(term: &Term, protocol: Protocol) -> Result<()> {
+    if protocol == Protocol::Kitty {
+        term.write_str(kitty_clear())?;
+    }
+    term.clear_screen()?;
+    Ok(())
+}
+
+struct Picker<'a> {
+    client: &'a Client,
+    term: Term,
+    protocol: Protocol,
+    prompt: &'a str,
+    items: &'a [String],
+    covers: &'a [Option<String>],
+    images: HashMap<usize, Option<Arc<DynamicImage>>>, // Item index -> cover, once it was tried
+    rendered: HashMap<usize, Vec<String>>, // Item index -> rendered cover
+}
+
+impl Picker<'_> {
+    // Downloads the covers of the rows on screen that weren't tried yet
+    async fn load(&mut self, rows: &[usize]) {
+        let missing: Vec<usize> = rows
+            .iter()
+            .copied()
+            .filter(|index| !self.images.contains_key(index))
+            .collect();
+        let client = self.client;
+        let covers = self.covers;
+        let loaded: Vec<(usize, Option<Arc<DynamicImage>>)> = stream::iter(missing)
+            .map(|index| async move {
+                let image = match covers.get(index).and_then(|url| url.as_deref()) {
+                    Some(url) => download(client, url).await,
+                    None => None,
+                };
+                (index, image)
+            })
+            .buffer_unordered(PARALLEL_DOWNLOADS)
+            .collect()
+            .await;
+        self.images.extend(loaded);
+    }
+
+    async fn run(mut self, default: usize) -> Result<Option<usize>> {
+        let matcher = SkimMatcherV2::default();
+        let mut search = String::new();
+        let mut position = default.min(self.items.len().saturating_sub(1));
+        let mut offset = 0;
+
+        self.term.hide_cursor()?;
+        // * Restores the terminal on every way out, errors included
+        let _restore = Restore {
+            term: self.term.clone(),
+            protocol: self.protocol,
+        };
+        let selection = loop {
+            // * Best matches first while searching, the original order otherwise
+            let shown: Vec<usize> = if search.is_empty() {
+                (0..self.items.len()).collect()
+            } else {
+                let mut scored: Vec<(i64, usize)> = self
+                    .items
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, item)| matcher.fuzzy_match(item, &search).map(|s| (s, i)))
+                    .collect();
+                scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+                scored.into_iter().map(|(_, i)| i).collect()
+            };
+            position = position.min(shown.len().saturating_sub(1));
+
+            let (height, _) = self.term.size();
+            let visible = (height as usize).saturating_sub(2).max(1);
+            if position < offset {
+                offset = position;
+            } else if position >= offset + visible {
+                offset = position + 1 - visible;
+            }
+            let on_screen: Vec<usize> = shown.iter().copied().skip(offset).take(visible).collect();
+            self.load(&on_screen).await;
+            self.draw(&search, &shown, position, offset, visible)?;
+
+            match self.term.read_key()? {
+                Key::ArrowUp if position > 0 => position -= 1,
+                Key::ArrowDown if position + 1 < shown.len() => position += 1,
+                Key::PageUp => position = position.saturating_sub(visible),
+                Key::PageDown => position = (position + visible).min(shown.len().saturating_sub(1)),
+                Key::Home => position = 0,
+                Key::End => position = shown.len().saturating_sub(1),
+                Key::Enter if !shown.is_empty() => break Some(shown[position]),
+                Key::Escape => break None,
+                Key::Backspace => {
+                    search.pop();
+                    position = 0;
+                }
+                Key::Char(c) if !c.is_control() => {
+                    search.push(c);
+                    position = 0;
+                    offset = 0;
+                }
+                _ => {}
+            }
+        };
+
+        Ok(selection)
+    }
+
+    fn clear(&self) -> Result<()> {
+        clear(&self.term, self.protocol)
+    }
+
+    fn draw(
+        &mut self,
+        search: &str,
+        shown: &[usize],
+        position: usize,
+        offset: usize,
+        visible: usize,
+    ) -> Result<()> {
+        self.clear()?;
+        let (_, width) = self.term.size();
+        let list_width = (width as usize).saturating_sub(LIST_COVER_COLUMNS + 3);
+
+        let mut screen = format!("{} {}\n", style(self.prompt).cyan().bold(), search);
+        for (row, &index) in shown.iter().enumerate().skip(offset).take(visible) {
+            let marker = if row == position {
+                style(">>").bold().to_string()
+            } else {
+                "  ".to_string()
+            };
+            let text = console::truncate_str(&self.items[index], list_width.saturating_sub(3), "…");
+            screen.push_str(&format!("{} {}\n", marker, text));
+        }
+
+        // * The cover goes to the right of the list, every line is placed by hand
+        if let Some(&index) = shown.get(position)
+            && let Some(Some(image)) = self.images.get(&index)
+        {
+            let protocol = self.protocol;
+            let lines = self
+                .rendered
+                .entry(index)
+                .or_insert_with(|| render(image, protocol, LIST_COVER_COLUMNS, LIST_COVER_ROWS));
+            let column = list_width + 2;
+            for (row, line) in lines.iter().enumerate() {
+                screen.push_str(&format!("\x1b[{};{}H{}", row + 2, column, line));
+            }
+        }
+        self.term.write_str(&screen)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+// Episode labels
+// Episodes as the providers label them, regular ones count on AniList and the rest are specials
+
+// =============== Imports ================
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+// An episode the way the provider labels it, "12", "6.5" for recaps or "0" for prologues
+// Only whole episodes from 1 up count on AniList, the rest are specials
+#[derive(Debug, Clone)]
+pub struct Episode(String);
+
+impl Episode {
+    pub fn new(label: &str) -> Self {
+        let label = label.trim();
+        // * "07" and "7" are the same episode
+        match label.parse::<u32>() {
+            Ok(number) => Self(number.to_string()),
+            Err(_) => Self(label.to_string()),
+        }
+    }
+
+    // Number of a regular episode, None for specials
+    pub fn number(&self) -> Option<u32> {
+        self.0.parse().ok().filter(|&n| n > 0)
+    }
+
+    pub fn is_special(&self) -> bool {
+        self.number().is_none()
+    }
+
+    // AniList progress once this episode is watched, specials count as the episode before them
+    pub fn progress(&self) -> u32 {
+        match self.value() {
+            Some(value) if value > 0.0 => value.floor() as u32,
+            _ => 0,
+        }
+    }
+
+    // Whether the user is past this episode with the given AniList progress
+    pub fn watched(&self, progress: u32) -> bool {
+        match self.number() {
+            Some(number) => number <= progress,
+            None => self.value().is_some() && self.progress() < progress,
+        }
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.0.parse::<f64>().ok().filter(|v| v.is_finite())
+    }
+}
+
+impl From<u32> for Episode {
+    fn from(number: u32) -> Self {
+        Self(number.to_string())
+    }
+}
+
+impl fmt::Display for Episode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(&self.0)
+    }
+}
+
+// * Equality, hashing and order all go by the value, so "6.50" and "6.5" are the same episode
+impl PartialEq for Episode {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Episode {}
+
+impl Hash for Episode {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self.value() {
+            Some(value) => value.to_bits().hash(state),
+            None => self.0.hash(state),
+        }
+    }
+}
+
+// Episodes in airing order, labels that aren't numbers go last
+impl Ord for Episode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.value(), other.value()) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => self.0.cmp(&other.0),
+        }
+    }
+}
+
+impl PartialOrd for Episode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// * Regular episodes are saved as numbers, so older progress files stay readable both ways
+impl Serialize for Episode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0.parse::<u32>() {
+            Ok(number) => serializer.serialize_u32(number),
+            Err(_) => serializer.serialize_str(&self.0),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Episode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Label {
+            Number(u32),
+            Text(String),
+        }
+
+        Ok(match Label::deserialize(deserializer)? {
+            Label::Number(number) => Self::from(number),
+            Label::Text(label) => Self::new(&label),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::local_save::ProgressDatabase;
+    use std::collections::HashSet;
+
+    #[test]
+    fn orders_by_value_with_text_last() {
+        let mut episodes: Vec<Episode> = ["10", "2", "6.5", "0", "OVA", "1"]
+            .iter()
+            .map(|label| Episode::new(label))
+            .collect();
+        episodes.sort();
+        let labels: Vec<String> = episodes.iter().map(|e| e.to_string()).collect();
+        assert_eq!(labels, ["0", "1", "2", "6.5", "10", "OVA"]);
+    }
+
+    #[test]
+    fn equality_and_hash_follow_the_order() {
+        assert_eq!(Episode::new("07"), Episode::from(7));
+        assert_eq!(Episode::new("6.50"), Episode::new("6.5"));
+        assert_ne!(Episode::new("6.5"), Episode::new("6.6"));
+
+        let set: HashSet<Episode> = [Episode::new("6.50"), Episode::new("6.5"), Episode::new("7")]
+            .into_iter()
+            .collect();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn only_whole_episodes_from_one_count() {
+        assert_eq!(Episode::new("12").number(), Some(12));
+        assert!(Episode::new("0").is_special());
+        assert!(Episode::new("6.5").is_special());
+        assert!(Episode::new("OVA").is_special());
+        assert_eq!(Episode::new("6.5").progress(), 6);
+        assert_eq!(Episode::new("0").progress(), 0);
+        assert_eq!(Episode::new("OVA").progress(), 0);
+    }
+
+    #[test]
+    fn watched_compares_with_the_progress() {
+        assert!(Episode::from(5).watched(5));
+        assert!(!Episode::from(6).watched(5));
+        // * A special is watched once the episode after it is
+        assert!(!Episode::new("5.5").watched(5));
+        assert!(Episode::new("5.5").watched(6));
+        assert!(Episode::new("0").watched(1));
+        assert!(!Episode::new("0").watched(0));
+        assert!(!Episode::new("OVA").watched(100));
+    }
+
+    #[test]
+    fn reads_and_writes_old_progress_files() {
+        let old = r#"{"entries":[{"anilist_id":1,"episode":3,"position":12.5,"scraper_ids":{}}]}"#;
+        let db: ProgressDatabase = serde_json::from_str(old).unwrap();
+        assert_eq!(db.entries[0].episode, Episode::from(3));
+        assert_eq!(db.entries[0].updated_at, 0);
+
+        // * Regular episodes stay numbers, specials become strings
+        let json = serde_json::to_value(&db).unwrap();
+        assert_eq!(json["entries"][0]["episode"], serde_json::json!(3));
+        assert_eq!(serde_json::to_value(Episode::new("6.5")).unwrap(), serde_json::json!("6.5"));
+        let special: Episode = serde_json::from_str("\"6.5\"").unwrap();
+        assert_eq!(special, Episode::new("6.5"));
+    }
+}
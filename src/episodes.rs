@@ -0,0 +1,140 @@
+// Episode picker
+// Episodes the provider has, with their Jikan titles and air dates, marked if watched, started or filler
+
+// =============== Imports ================
+use crate::api;
+use crate::api::anilist::user_fetch::AnimeData;
+use crate::api::jikan::EpisodeMap;
+use crate::config;
+use crate::episode::Episode;
+use crate::local_save;
+use crate::scraping;
+use crate::theme;
+use crate::utils;
+
+use anyhow::Result;
+use console::style;
+use dialoguer::FuzzySelect;
+use reqwest::Client;
+
+// =============== Picker ===============
+
+// Episodes the provider has, falls back to the aired episodes on AniList
+async fn available(
+    client: &Client,
+    config: &config::Config,
+    anime: &AnimeData,
+    jikan: &EpisodeMap,
+) -> Result<Vec<Episode>> {
+    if config.language == "english" {
+        match scraping::eng_scraping::available_episodes(
+            client,
+            &config.language,
+            anime.id,
+            &config.sub_or_dub,
+            &anime.title,
+        )
+        .await
+        {
+            Ok(episodes) if !episodes.is_empty() => return Ok(episodes),
+            Ok(_) => {}
+            Err(e) => log::warn!("No episode list from AllAnime for {}: {:#}", anime.id, e),
+        }
+    }
+
+    let data = api::anilist::fetch::data_by_id(client, anime.id).await?;
+    let aired = match data.aired_episodes() {
+        0 => jikan.episodes.keys().copied().max().unwrap_or(0),
+        aired => aired,
+    };
+    Ok((1..=aired).map(Episode::from).collect())
+}
+
+fn row(
+    episode: &Episode,
+    anime: &AnimeData,
+    jikan: &EpisodeMap,
+    started: Option<&(Episode, f64)>,
+) -> String {
+    let marker = match started {
+        _ if episode.watched(anime.progress) => style("✓".to_string()).green(),
+        Some((ep, position)) if ep == episode && *position > 0.0 => {
+            let position = position.round() as u64;
+            style(format!("◐ {:02}:{:02}", position / 60, position % 60)).yellow()
+        }
+        _ => style(" ".to_string()),
+    };
+
+    // * Jikan only knows the regular episodes
+    let number = episode.number();
+    let info = number.and_then(|n| jikan.get(n));
+    let mut row = format!("{:<7} {:>4}.", marker, episode);
+    if episode.is_special() {
+        row.push_str(&format!(" {}", style("[special]").cyan()));
+    }
+    if let Some(title) = info.and_then(|i| i.title.as_deref()) {
+        row.push_str(&format!(" {}", title));
+    }
+    // * Jikan dates look like 2013-04-07T00:00:00+00:00, the day is enough
+    if let Some(aired) = info.and_then(|i| i.aired.as_deref()) {
+        row.push_str(&format!(" | {}", aired.get(..10).unwrap_or(aired)));
+    }
+    if number.is_some_and(|n| jikan.is_filler(n)) {
+        row.push_str(&format!(" {}", style("[filler]").red()));
+    }
+    if number.is_some_and(|n| jikan.is_recap(n)) {
+        row.push_str(&format!(" {}", style("[recap]").red()));
+    }
+    row
+}
+
+// Lets the user pick an episode of the anime, None if they backed out
+pub async fn pick(
+    client: &Client,
+    config: &config::Config,
+    anime: &AnimeData,
+) -> Result<Option<Episode>> {
+    println!("Getting the episode list...");
+    // * Titles and filler marks are nice to have, the list works without them
+    let jikan = match api::anilist::fetch::id_converter(client, anime.id).await {
+        Ok(mal_id) => api::jikan::episodes(client, mal_id)
+            .await
+            .unwrap_or_else(|e| {
+                log::warn!("No Jikan episode data for {}: {:#}", anime.id, e);
+                EpisodeMap::default()
+            }),
+        Err(e) => {
+            log::warn!("{:#}", e);
+            EpisodeMap::default()
+        }
+    };
+    let episodes = available(client, config, anime, &jikan).await?;
+    utils::clear();
+    if episodes.is_empty() {
+        println!("No episodes are out yet.");
+        return Ok(None);
+    }
+
+    let db = local_save::ProgressDatabase::load()?;
+    let started = db
+        .get_entry(anime.id)
+        .and_then(|e| e.position_for(anime.rewatching));
+    let rows: Vec<String> = episodes
+        .iter()
+        .map(|episode| row(episode, anime, &jikan, started.as_ref()))
+        .collect();
+    // * The first episode that isn't watched yet is the one most likely wanted
+    let next = episodes
+        .iter()
+        .position(|episode| !episode.watched(anime.progress))
+        .unwrap_or(episodes.len() - 1);
+
+    let theme = theme::CustomTheme {};
+    let selection = FuzzySelect::with_theme(&theme)
+        .with_prompt(format!("{} - choose an episode:", anime.title))
+        .items(&rows)
+        .default(next)
+        .interact_opt()?;
+    utils::clear();
+    Ok(selection.map(|index| episodes[index].clone()))
+}
@@ -0,0 +1,84 @@
+// Every anime of a franchise in a suggested watch order
+// Prequels, sequels, side stories, movies, OVAs and specials, marked with the user's list status
+
+// =============== Imports ================
+use crate::api::anilist::fetch::{self, FranchiseEntry};
+use crate::api::anilist::{list_entry, mutation, user_fetch};
+use crate::cover;
+use crate::utils;
+
+use anyhow::Result;
+use console::style;
+use reqwest::Client;
+
+fn row(position: usize, entry: &FranchiseEntry) -> String {
+    let year = entry
+        .start_date
+        .year
+        .map(|y| y.to_string())
+        .unwrap_or_else(|| "?".to_string());
+    let episodes = entry
+        .episodes
+        .map(|e| format!("{} ep", e))
+        .unwrap_or_else(|| "? ep".to_string());
+    let side = match entry.relation.as_deref() {
+        Some("SIDE_STORY") => " (side story)",
+        _ => "",
+    };
+    let status = match entry.list_entry.as_ref().and_then(|e| e.status.as_deref()) {
+        Some("COMPLETED") => style("Completed".to_string()).green(),
+        Some(status) => {
+            let progress = entry.list_entry.as_ref().and_then(|e| e.progress).unwrap_or(0);
+            style(format!("{} {}", mutation::status_label(status), progress)).yellow()
+        }
+        None => style("Not on list".to_string()).dim(),
+    };
+    format!(
+        "{:>2}. {}{} | {} | {} | {} | {}",
+        position + 1,
+        entry.title,
+        side,
+        entry.format.as_deref().unwrap_or("?"),
+        year,
+        episodes,
+        status
+    )
+}
+
+// Shows the franchise of the anime, returns the anime to watch if one was chosen
+pub async fn navigator(client: &Client, media_id: i32) -> Result<Option<user_fetch::AnimeData>> {
+    println!("Walking the franchise...");
+    let entries = fetch::franchise(client, media_id).await?;
+    utils::clear();
+
+    let rows: Vec<String> = entries
+        .iter()
+        .enumerate()
+        .map(|(position, entry)| row(position, entry))
+        .collect();
+    let covers: Vec<Option<String>> = entries.iter().map(|e| e.cover.clone()).collect();
+    // * The first anime that isn't completed yet is the one to watch next
+    let next = entries.iter().position(|e| !e.completed()).unwrap_or(0);
+
+    loop {
+        let prompt = format!("Watch order ({} anime):", entries.len());
+        let selection = cover::select(client, &prompt, &rows, &covers, next).await?;
+        utils::clear();
+        let Some(index) = selection else {
+            return Ok(None);
+        };
+
+        let entry = &entries[index];
+        if let Some(data) = list_entry::watch_or_add(
+            client,
+            entry.id,
+            &entry.title,
+            entry.episodes,
+            entry.list_entry.as_ref(),
+        )
+        .await?
+        {
+            return Ok(Some(data));
+        }
+    }
+}
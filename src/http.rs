@@ -0,0 +1,189 @@
+// Shared request layer for every API and scraper
+// Handles timeouts, retries with backoff, Retry-After and rate limiting per host
+
+// =============== Imports ================
+use anyhow::{Context, Result};
+use reqwest::{RequestBuilder, Response, StatusCode, header::RETRY_AFTER};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::time::{Duration, Instant, sleep_until};
+
+// Longest time we are willing to wait because of a Retry-After header
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+const BACKOFF_BASE: Duration = Duration::from_millis(500);
+const BACKOFF_MAX: Duration = Duration::from_secs(10);
+
+// * The earliest time the next request can be sent to each host
+static NEXT_SLOT: Mutex<Option<HashMap<String, Instant>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Service {
+    AniList,
+    Jikan,
+    AniSkip,
+    AllAnime,
+    AnimeDrive,
+    Other,
+}
+
+impl Service {
+    fn timeout(self) -> Duration {
+        match self {
+            Service::AniList => Duration::from_secs(20),
+            Service::Jikan => Duration::from_secs(20),
+            Service::AniSkip => Duration::from_secs(10),
+            Service::AllAnime => Duration::from_secs(30),
+            // ! AnimeDrive is really slow, if they finally fix their site I will lower it
+            Service::AnimeDrive => Duration::from_secs(120),
+            Service::Other => Duration::from_secs(30),
+        }
+    }
+
+    // Minimum time between two requests to the same host
+    fn min_interval(self) -> Duration {
+        match self {
+            Service::AniList => Duration::from_millis(700), // 90 requests per minute
+            Service::Jikan => Duration::from_millis(400),   // 3 requests per second
+            Service::AnimeDrive => Duration::from_millis(500),
+            _ => Duration::ZERO,
+        }
+    }
+
+    fn max_retries(self) -> u32 {
+        match self {
+            Service::Jikan => 4,
+            Service::AnimeDrive => 1,
+            _ => 3,
+        }
+    }
+}
+
+// Sends the request, retrying on timeouts, connection errors, 429s and server errors
+// Responses with other error statuses are returned as they are, so callers can handle them
+pub async fn send(service: Service, request: RequestBuilder) -> Result<Response> {
+    let max_retries = service.max_retries();
+    let host = host_of(&request);
+
+    for attempt in 0..=max_retries {
+        let req = request
+            .try_clone()
+            .ok_or_else(|| anyhow::anyhow!("Request can't be retried"))?
+            .timeout(service.timeout());
+
+        wait_for_slot(&host, service.min_interval()).await;
+
+        match req.send().await {
+            Ok(res) => {
+                let status = res.status();
+                let retryable =
+                    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+                if !retryable || attempt == max_retries {
+                    return Ok(res);
+                }
+
+                let wait = retry_after(&res).unwrap_or_else(|| backoff(attempt));
+                log::warn!(
+                    "{:?} returned {}, retrying in {:?} ({}/{})",
+                    service,
+                    status,
+                    wait,
+                    attempt + 1,
+                    max_retries
+                );
+                push_slot(&host, wait);
+            }
+            Err(e) if attempt < max_retries && (e.is_timeout() || e.is_connect()) => {
+                let wait = backoff(attempt);
+                log::warn!(
+                    "Request to {:?} failed: {}, retrying in {:?} ({}/{})",
+                    service,
+                    e,
+                    wait,
+                    attempt + 1,
+                    max_retries
+                );
+                push_slot(&host, wait);
+            }
+            Err(e) => {
+                if e.is_timeout() {
+                    log::warn!("Internet connection error: {}", e);
+                }
+                return Err(e).with_context(|| format!("Request to {:?} failed", service));
+            }
+        }
+    }
+
+    unreachable!("the last attempt always returns")
+}
+
+// Runs `f` until it succeeds, at most `attempts` times, waiting more and more between tries
+// Used for things that fail as a whole (like getting an episode link), not single requests
+pub async fn retry<T, F, Fut>(attempts: u32, mut f: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut last_err = anyhow::anyhow!("No attempts were made");
+    for attempt in 0..attempts {
+        if attempt > 0 {
+            tokio::time::sleep(backoff(attempt)).await;
+        }
+        match f().await {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                log::warn!("Attempt {}/{} failed: {:#}", attempt + 1, attempts, e);
+                last_err = e;
+            }
+        }
+    }
+    Err(last_err.context(format!("Gave up after {} tries", attempts)))
+}
+
+fn host_of(request: &RequestBuilder) -> String {
+    request
+        .try_clone()
+        .and_then(|r| r.build().ok())
+        .and_then(|r| r.url().host_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+async fn wait_for_slot(host: &str, interval: Duration) {
+    let slot = {
+        let mut slots = NEXT_SLOT.lock().unwrap();
+        let slots = slots.get_or_insert_with(HashMap::new);
+        let now = Instant::now();
+        let slot = slots.get(host).copied().unwrap_or(now).max(now);
+        slots.insert(host.to_string(), slot + interval);
+        slot
+    };
+    sleep_until(slot).await;
+}
+
+// Nobody sends anything to the host until the wait is over
+fn push_slot(host: &str, wait: Duration) {
+    let mut slots = NEXT_SLOT.lock().unwrap();
+    let slots = slots.get_or_insert_with(HashMap::new);
+    let until = Instant::now() + wait;
+    let slot = slots.entry(host.to_string()).or_insert(until);
+    *slot = (*slot).max(until);
+}
+
+fn retry_after(res: &Response) -> Option<Duration> {
+    let seconds: u64 = res.headers().get(RETRY_AFTER)?.to_str().ok()?.trim().parse().ok()?;
+    Some(Duration::from_secs(seconds).min(MAX_RETRY_AFTER))
+}
+
+// Exponential backoff with some jitter, so parallel requests don't retry at the same time
+fn backoff(attempt: u32) -> Duration {
+    let base = BACKOFF_BASE
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(BACKOFF_MAX);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    let jitter = base.mul_f64(f64::from(nanos % 1000) / 2000.0);
+    base + jitter
+}
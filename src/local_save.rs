@@ -1,6 +1,10 @@
 // =============== Imports ================
+use crate::episode::Episode;
+use crate::profile;
+use crate::utils;
+
 use std::collections::HashMap;
-use std::fs::{create_dir_all, File};
+use std::fs::File;
 use std::io::{Read, Write};
 use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
@@ -11,9 +15,31 @@ use log;
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct WatchProgress {
     pub anilist_id: i32,
-    pub episode: u32,
+    pub episode: Episode,
     pub position: f64,
     pub scraper_ids: HashMap<String, String>, // language -> scraper_id
+    #[serde(default)]
+    pub updated_at: u64, // Unix timestamp of the last change, 0 for entries from older versions
+    #[serde(default)]
+    pub rewatch: Option<RewatchProgress>, // Kept apart, so a rewatch doesn't overwrite the first watch
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RewatchProgress {
+    pub episode: Episode,
+    pub position: f64,
+    pub updated_at: u64,
+}
+
+impl WatchProgress {
+    // Episode and position of the watch (or rewatch) in progress
+    pub fn position_for(&self, rewatching: bool) -> Option<(Episode, f64)> {
+        if rewatching {
+            self.rewatch.as_ref().map(|r| (r.episode.clone(), r.position))
+        } else {
+            Some((self.episode.clone(), self.position))
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
@@ -23,11 +49,8 @@ pub struct ProgressDatabase {
 
 impl ProgressDatabase {
     pub fn config_path() -> Result<PathBuf> {
-        let mut path = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("."));
-        path.push("yato");
-        create_dir_all(&path)?;
-        path.push("progress.json");
-        Ok(path)
+        // * Every profile has its own progress
+        Ok(profile::data_dir()?.join("progress.json"))
     }
 
     pub fn load() -> Result<Self> {
@@ -59,24 +82,88 @@ impl ProgressDatabase {
         Ok(())
     }
 
-    pub fn update_or_add(&mut self, anilist_id: i32, episode: u32, position: f64, language: &str, scraper_id: &str) {
+    pub fn update_or_add(&mut self, anilist_id: i32, episode: &Episode, position: f64, language: &str, scraper_id: &str) {
         if let Some(entry) = self.entries.iter_mut().find(|e| e.anilist_id == anilist_id) {
             entry.position = position;
-            entry.episode = episode;
+            entry.episode = episode.clone();
             entry.scraper_ids.insert(language.to_string(), scraper_id.to_string());
+            entry.updated_at = utils::now();
         } else {
             let mut scraper_ids = HashMap::new();
             scraper_ids.insert(language.to_string(), scraper_id.to_string());
 
             self.entries.push(WatchProgress {
                 anilist_id,
-                episode,
+                episode: episode.clone(),
                 position,
                 scraper_ids,
+                updated_at: utils::now(),
+                rewatch: None,
+            });
+        }
+    }
+
+    // Remembers the scraper id without touching the progress
+    // * New entries get no timestamp, so syncing takes AniList's progress for them
+    pub fn set_scraper_id(&mut self, anilist_id: i32, language: &str, scraper_id: &str) {
+        match self.entries.iter_mut().find(|e| e.anilist_id == anilist_id) {
+            Some(entry) => {
+                entry.scraper_ids.insert(language.to_string(), scraper_id.to_string());
+            }
+            None => self.entries.push(WatchProgress {
+                anilist_id,
+                episode: Episode::from(0),
+                position: 0.0,
+                scraper_ids: HashMap::from([(language.to_string(), scraper_id.to_string())]),
+                updated_at: 0,
+                rewatch: None,
+            }),
+        }
+    }
+
+    // Saves where the user is, into the rewatch progress if they are rewatching
+    pub fn save_position(&mut self, anilist_id: i32, rewatching: bool, episode: &Episode, position: f64, language: &str, scraper_id: &str) {
+        if !rewatching {
+            self.update_or_add(anilist_id, episode, position, language, scraper_id);
+            return;
+        }
+        if self.get_entry(anilist_id).is_none() {
+            self.update_or_add(anilist_id, &Episode::from(0), 0.0, language, scraper_id);
+        }
+        if let Some(entry) = self.entries.iter_mut().find(|e| e.anilist_id == anilist_id) {
+            entry.scraper_ids.insert(language.to_string(), scraper_id.to_string());
+            entry.rewatch = Some(RewatchProgress {
+                episode: episode.clone(),
+                position,
+                updated_at: utils::now(),
             });
         }
     }
 
+    // Drops the rewatch progress, when a rewatch starts over or is finished
+    pub fn clear_rewatch(&mut self, anilist_id: i32) {
+        if let Some(entry) = self.entries.iter_mut().find(|e| e.anilist_id == anilist_id) {
+            entry.rewatch = None;
+        }
+    }
+
+    // Moves the entry to another episode, used when AniList has newer progress
+    pub fn set_episode(&mut self, anilist_id: i32, rewatching: bool, episode: u32, updated_at: u64) {
+        if let Some(entry) = self.entries.iter_mut().find(|e| e.anilist_id == anilist_id) {
+            if rewatching {
+                entry.rewatch = Some(RewatchProgress {
+                    episode: Episode::from(episode),
+                    position: 0.0,
+                    updated_at,
+                });
+            } else {
+                entry.episode = Episode::from(episode);
+                entry.position = 0.0;
+                entry.updated_at = updated_at;
+            }
+        }
+    }
+
     pub fn get_entry(&self, anilist_id: i32) -> Option<&WatchProgress> {
         self.entries.iter().find(|entry| entry.anilist_id == anilist_id)
     }
@@ -86,3 +173,4 @@ impl ProgressDatabase {
             .and_then(|entry| entry.scraper_ids.get(language).map(String::as_str))
     }
 }
+
@@ -16,43 +16,54 @@
 // Final version: 2025.04.27.
 
 // =============== Imports ================
+mod airing;
 mod api;
 mod args;
 mod config;
+mod cover;
+mod episode;
+mod episodes;
 mod discord_rpc;
+mod franchise;
+mod http;
 mod local_save;
 mod mpvipc;
 mod player;
+mod profile;
+mod recommend;
 mod scraping;
+mod seasonal;
 mod skip_override;
+mod sync;
 mod theme;
 mod utils;
 
+use episode::Episode;
+
 use anyhow::{Context, Result};
 use dialoguer::{Input, MultiSelect, Select};
 use discord_rpc_client;
 use reqwest::{Client, ClientBuilder};
-use std::{collections::HashMap, default, io, process};
-use tokio::{
-    self,
-    time::{Duration, sleep},
-};
+use std::{collections::HashMap, default, io, path::Path, process};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     utils::init_log()?; // Initialize logging
     log::info!("Application started");
 
-    // ! Creating client with a 120 second timeout (needed for hun_scraping, if they finally fix their site I will remove it)
+    // * Timeouts are set per service in the http module
     let client = ClientBuilder::new()
-        .timeout(std::time::Duration::from_secs(120))
+        .connect_timeout(std::time::Duration::from_secs(10))
         .build()
         .with_context(|| "Failed to create HTTP client")?;
+    let matches = args::parse();
+    profile::init(matches.get_one::<String>("profile").map(String::as_str))?;
     let mut config = config::load_config();
 
     config::test(&config)?; // Testing if the config file is valid
+    api::anilist::graphql::init_title_language(&client).await;
 
-    let (matches, rpc_client) = args::handle_args(&mut config, &client).await?;
+    let (matches, rpc_client) = args::handle_args(matches, &mut config, &client).await?;
     {
         utils::check_network(&client).await?; // Checking if the network is available
 
@@ -81,8 +92,9 @@ async fn main() -> Result<()> {
                         title: name.clone(),
                         progress: episode_number,
                         episodes: max_ep,
+                        rewatching: false,
                     };
-                    watch(&client, config, rpc_client, info, false).await?;
+                    watch(&client, config, rpc_client, info, false, None).await?;
                 }
                 Err(_) => {
                     let anime_id =
@@ -95,14 +107,30 @@ async fn main() -> Result<()> {
                         title: name.clone(),
                         progress: episode_number,
                         episodes: max_ep,
+                        rewatching: false,
                     };
-                    watch(&client, config, rpc_client, info, false).await?;
+                    watch(&client, config, rpc_client, info, false, None).await?;
                 }
             }
             return Ok(());
         }
 
         api::anilist::user_fetch::check_credentials(&client).await?; // * Credentials are only needed after this part
+        // * Sending what couldn't be sent last time, then retrying every few minutes
+        api::anilist::outbox::flush_on_start(&client).await;
+        api::anilist::outbox::spawn_background_flush(client.clone());
+        if matches.get_flag("outbox") {
+            api::anilist::outbox::interactive(&client).await?;
+            return Ok(());
+        }
+        if matches.get_flag("sync") {
+            sync::run(&client, matches.get_flag("dry-run"), false).await?;
+            return Ok(());
+        }
+        if let Some(path) = matches.get_one::<String>("ics") {
+            airing::export_ics(&client, Path::new(path)).await?;
+            return Ok(());
+        }
         if matches.get_flag("new") {
             add_new_anime(&client).await?;
         }
@@ -113,15 +141,26 @@ async fn main() -> Result<()> {
 
     let options = vec![
         "Continue Watching",
+        "Choose an episode",
         "Edit (Episodes, Status, Score, Skipping)",
         "Info",
         "Add anime to list",
+        "This season",
+        "Recommendations",
+        "Franchise watch order",
+        "Rewatch a completed anime",
+        "Airing schedule",
         "Exit",
     ];
     let theme = theme::CustomTheme {};
+    let prompt = if profile::is_default() {
+        "Select an option:".to_string()
+    } else {
+        format!("Select an option ({}):", profile::active())
+    };
 
     let select_options = Select::with_theme(&theme)
-        .with_prompt("Select an option:")
+        .with_prompt(prompt)
         .default(0)
         .items(&options)
         .interact_opt()?;
@@ -137,20 +176,62 @@ async fn main() -> Result<()> {
         }
         continue_watching(&client, config, rpc_client.clone()).await?;
     } else if select_options == Some(1) {
+        // Any episode of an anime on the list
+        if config.discord_presence {
+            discord_rpc::selecting(&rpc_client, "Choosing an episode", "");
+        }
+        choose_episode(&client, config, rpc_client.clone()).await?;
+    } else if select_options == Some(2) {
         // Edit (Episodes, Status, Score, Skipping)
         if config.discord_presence {
             discord_rpc::selecting(&rpc_client, "Updating their List", "");
         }
         update(&client).await?;
-    } else if select_options == Some(2) {
+    } else if select_options == Some(3) {
         // Information about an anime
         info(&client).await?;
-    } else if select_options == Some(3) {
+    } else if select_options == Some(4) {
         // Add new anime
         if config.discord_presence {
             discord_rpc::selecting(&rpc_client, "Thinking what to watch next", "");
         }
         add_new_anime(&client).await?;
+    } else if select_options == Some(5) {
+        // Browse this season's anime
+        if config.discord_presence {
+            discord_rpc::selecting(&rpc_client, "Browsing this season", "");
+        }
+        utils::clear();
+        if let Some(info) = seasonal::browse(&client).await? {
+            watch(&client, config, rpc_client.clone(), info, true, None).await?;
+        }
+    } else if select_options == Some(6) {
+        // Recommendations based on the best rated anime
+        if config.discord_presence {
+            discord_rpc::selecting(&rpc_client, "Looking for something new", "");
+        }
+        utils::clear();
+        recommend::menu(&client).await?;
+    } else if select_options == Some(7) {
+        // Prequels, sequels and side stories of an anime
+        utils::clear();
+        let anime_name: String = Input::with_theme(&theme)
+            .with_prompt("Enter the name of the anime")
+            .interact_text()?;
+        let anime_id = api::anilist::fetch::search(&client, anime_name).await?;
+        if let Some(info) = franchise::navigator(&client, anime_id).await? {
+            watch(&client, config, rpc_client.clone(), info, true, None).await?;
+        }
+    } else if select_options == Some(8) {
+        // Rewatch something already completed
+        if config.discord_presence {
+            discord_rpc::selecting(&rpc_client, "Picking something to rewatch", "");
+        }
+        rewatch(&client, config, rpc_client.clone()).await?;
+    } else if select_options == Some(9) {
+        // What's airing and when
+        utils::clear();
+        airing::show(&client).await?;
     } else {
         // Exit
         utils::clear();
@@ -171,11 +252,12 @@ async fn info(client: &Client) -> Result<()> {
         .interact_text()?;
     let search = api::anilist::fetch::search(&client, anime_name).await;
     utils::clear();
-    if search.is_err() {
-        eprintln!("Error: {}", search.unwrap_err());
-        process::exit(1);
-    } else {
-        api::anilist::fetch::information(&client, search.unwrap()).await?;
+    match search {
+        Ok(anime_id) => api::anilist::fetch::information(client, anime_id, false).await?,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
     }
 
     Ok(())
@@ -222,16 +304,82 @@ async fn add_new_anime(client: &Client) -> Result<()> {
     Ok(())
 }
 
+// Syncs without the "in sync" message, a report of what changed stays until Enter is pressed
+async fn sync_quietly(client: &Client, before: &str) {
+    match sync::run(client, false, true).await {
+        Ok(0) => {}
+        Ok(_) => {
+            println!("Press Enter to continue...");
+            let _ = io::stdin().read_line(&mut String::new());
+        }
+        Err(e) => log::warn!("Sync before {} failed: {:#}", before, e),
+    }
+}
+
 async fn continue_watching(
     client: &Client,
     config: config::Config,
     rpc_client: discord_rpc_client::Client,
 ) -> Result<()> {
+    // * Progress could have changed on another device since the last time
+    sync_quietly(client, "continuing").await;
     utils::clear();
     let info = api::anilist::user_fetch::current(&client).await?;
     utils::clear();
 
-    watch(&client, config, rpc_client, info, true).await?;
+    watch(client, config, rpc_client, info, true, None).await?;
+
+    Ok(())
+}
+
+// Lets the user pick the episode to play from the provider's episode list
+async fn choose_episode(
+    client: &Client,
+    config: config::Config,
+    rpc_client: discord_rpc_client::Client,
+) -> Result<()> {
+    // * The watched marks come from the progress, so it's brought up to date first
+    sync_quietly(client, "choosing an episode").await;
+    utils::clear();
+    let info = api::anilist::user_fetch::current(client).await?;
+    utils::clear();
+    let Some(episode) = episodes::pick(client, &config, &info).await? else {
+        return Ok(());
+    };
+
+    // * Watching an episode again shouldn't lower the progress on AniList, watch decides per episode
+    if episode.watched(info.progress) {
+        println!(
+            "Episode {} is already watched, progress is synced again after episode {}.",
+            episode, info.progress
+        );
+    }
+    watch(client, config, rpc_client, info, true, Some(episode)).await?;
+
+    Ok(())
+}
+
+// Sets a completed anime to REPEATING and starts it from the first episode
+// The first watch stays in the local database, the rewatch is tracked next to it
+async fn rewatch(
+    client: &Client,
+    config: config::Config,
+    rpc_client: discord_rpc_client::Client,
+) -> Result<()> {
+    utils::clear();
+    let mut info = api::anilist::user_fetch::completed(client).await?;
+    utils::clear();
+
+    api::anilist::mutation::update_status(client, info.id, api::anilist::mutation::REPEATING).await?;
+    api::anilist::mutation::update_progress(client, info.id, 0).await?;
+
+    let mut db = local_save::ProgressDatabase::load().with_context(|| "Failed to load progress database")?;
+    db.clear_rewatch(info.id);
+    db.save().with_context(|| "Failed to save progress database")?;
+
+    info.progress = 0;
+    info.rewatching = true;
+    watch(client, config, rpc_client, info, true, None).await?;
 
     Ok(())
 }
@@ -243,6 +391,7 @@ async fn update(client: &Client) -> Result<()> {
         "Change Status",
         "Change Score",
         "Override Skip Settings",
+        "Edit List Entry (Notes, Dates, Rewatches, Lists)",
     ];
     let theme = theme::CustomTheme {};
     let select_options = Select::with_theme(&theme)
@@ -261,10 +410,12 @@ async fn update(client: &Client) -> Result<()> {
             .with_prompt("Enter a new episode number")
             .interact_text()?;
         utils::clear();
-        api::anilist::mutation::update_progress(&client, anime_id, new_episode)
+        let delivery = api::anilist::mutation::update_progress(client, anime_id, new_episode)
             .await
             .with_context(|| format!("Failed to update progress to episode {}", new_episode))?;
-        println!("Progress updated!");
+        if delivery == api::anilist::outbox::Delivery::Sent {
+            println!("Progress updated!");
+        }
     } else if select_options == Some(1) {
         let anime_id = api::anilist::user_fetch::list_all(&client, 0).await?;
         utils::clear();
@@ -289,18 +440,11 @@ async fn update(client: &Client) -> Result<()> {
     } else if select_options == Some(2) {
         let anime_id = api::anilist::user_fetch::list_all(&client, 1).await?;
         utils::clear();
-        let theme = theme::CustomTheme {};
-        let new_score: f64 = Input::with_theme(&theme)
-            .with_prompt("Enter a new score on a scale of 1 to 10")
-            .interact_text()
-            .expect("Failed to read input, or input was invalid");
+        api::anilist::score::ask_and_update(client, anime_id).await?;
+    } else if select_options == Some(4) {
+        let anime_id = api::anilist::user_fetch::list_all(client, 3).await?;
         utils::clear();
-        if new_score < 1.0 || new_score > 10.0 {
-            eprintln!("Score must be between 1 and 10");
-            process::exit(1);
-        } else {
-            api::anilist::mutation::update_score(&client, anime_id, new_score).await?;
-        }
+        api::anilist::list_entry::interactive_edit(client, anime_id).await?;
     } else if select_options == Some(3) {
         if !skip_override::check_override() {
             println!(
@@ -347,7 +491,7 @@ async fn update(client: &Client) -> Result<()> {
                 let anime_id = api::anilist::fetch::search(&client, input).await?;
                 utils::clear();
 
-                let options = vec!["Opening", "Credits", "Recap", "Filler"];
+                let options = vec!["Opening", "Credits", "Recap", "Recap episodes"];
                 let selection = MultiSelect::with_theme(&theme)
                     .with_prompt("What overrides do you want to enable?")
                     .items(&options)
@@ -360,7 +504,7 @@ async fn update(client: &Client) -> Result<()> {
                     let mut intro = false;
                     let mut outro = false;
                     let mut recap = false;
-                    let mut filler = false;
+                    let mut recap_episodes = false;
                     for i in selected {
                         if i == 0 {
                             intro = true;
@@ -369,10 +513,18 @@ async fn update(client: &Client) -> Result<()> {
                         } else if i == 2 {
                             recap = true;
                         } else if i == 3 {
-                            filler = true;
+                            recap_episodes = true;
                         }
                     }
-                    skip_override::add_override(anime_id, intro, outro, recap, filler);
+                    let filler_policy = skip_override::select_filler_policy(None)?;
+                    skip_override::add_override(
+                        anime_id,
+                        intro,
+                        outro,
+                        recap,
+                        recap_episodes,
+                        filler_policy,
+                    );
                 }
                 println!("Override has been successfully saved!");
             }
@@ -385,59 +537,68 @@ async fn update(client: &Client) -> Result<()> {
     Ok(())
 }
 
-async fn sequel(client: &Client, anime_id: i32) -> Result<i32> {
-    let sequel = api::anilist::fetch::sequel_data(&client, anime_id).await;
-    match sequel {
-        Ok(s) => {
-            println!("Sequel: {}\n", s.title);
-            let theme = theme::CustomTheme {};
-            let options = vec![
-                "Add sequel to \"Currently watching\"",
-                "Don't add sequel to my watchlist",
-            ];
-            let select = Select::with_theme(&theme)
-                .with_prompt("Would you like to add the sequel to watchlist?")
-                .items(&options)
-                .default(0)
-                .interact_opt()
-                .unwrap();
+// Asks what to continue with, the sequel or one of the side stories
+async fn sequel(
+    client: &Client,
+    anime_id: i32,
+    rewatching: bool,
+) -> Result<api::anilist::fetch::FranchiseEntry> {
+    let parts = api::anilist::fetch::next_parts(client, anime_id, rewatching)
+        .await
+        .with_context(|| "Failed to get sequel data")?;
+    if parts.is_empty() {
+        return Err(anyhow::anyhow!("No sequel found"));
+    }
 
-            utils::clear();
-            match select {
-                Some(id) => {
-                    if id == 0 {
-                        api::anilist::mutation::update_status(&client, s.id, 0).await?;
-
-                        let options = vec!["Yes", "No"];
-                        let select = Select::with_theme(&theme)
-                            .with_prompt("Would you like to continue watching with the sequel?")
-                            .items(&options)
-                            .default(0)
-                            .clear(true)
-                            .interact_opt()
-                            .unwrap();
-                        match select {
-                            Some(id) => {
-                                if id == 1 {
-                                    Err(anyhow::anyhow!(
-                                        "User chose not to continue watching with the sequel."
-                                    ))
-                                } else {
-                                    Ok(s.id)
-                                }
-                            }
-                            None => Err(anyhow::anyhow!("No selection was made")),
-                        }
-                    } else {
-                        println!("See you later!");
-                        process::exit(0);
-                    }
-                }
-                None => Err(anyhow::anyhow!("No selection made")),
-            }
-        }
-        Err(e) => Err(anyhow::anyhow!("Failed to get sequel data: {}", e)),
+    let mut options: Vec<String> = parts
+        .iter()
+        .map(|part| {
+            let relation = match part.relation.as_deref() {
+                Some("SEQUEL") => "Sequel",
+                _ => "Side story",
+            };
+            format!(
+                "{}: {} ({})",
+                relation,
+                part.title,
+                part.format.as_deref().unwrap_or("?")
+            )
+        })
+        .collect();
+    options.push("Nothing for now".to_string());
+
+    let theme = theme::CustomTheme {};
+    let select = Select::with_theme(&theme)
+        .with_prompt("What would you like to continue with?")
+        .items(&options)
+        .default(0)
+        .clear(true)
+        .interact_opt()?;
+    utils::clear();
+
+    match select {
+        Some(index) if index < parts.len() => Ok(parts.into_iter().nth(index).unwrap()),
+        Some(_) => Err(anyhow::anyhow!(
+            "User chose not to continue watching with the sequel."
+        )),
+        None => Err(anyhow::anyhow!("No selection was made")),
+    }
+}
+
+// What to tell the user when the episode isn't out yet, None if it is
+fn caught_up(data: &api::anilist::fetch::AnimeData, episode: u32) -> Option<String> {
+    if let Some(next) = data.next_airing
+        && !data.has_aired(episode)
+    {
+        return Some(format!(
+            "Caught up, episode {} airs in {}.",
+            next.episode,
+            next.countdown()
+        ));
     }
+    // * Without an airing schedule the episode count is all there is to go by
+    let known = data.known_episodes();
+    (known > 0 && episode > known).then(|| format!("Caught up, episode {} isn't out yet.", episode))
 }
 
 async fn watch(
@@ -445,149 +606,174 @@ async fn watch(
     config: config::Config,
     mut rpc_client: discord_rpc_client::Client,
     anime_data: api::anilist::user_fetch::AnimeData,
-    syncing: bool,
+    syncing: bool, // Whether AniList is updated at all, each episode still has to be past the progress
+    picked: Option<Episode>, // Episode chosen in the picker, None continues after the progress
 ) -> Result<()> {
     let mut cur_ep = anime_data.progress;
+    // AniList progress of the anime being watched, episodes up to it are only rewatched
+    let mut progress = anime_data.progress;
     let mut anime_id = anime_data.id;
     let mut max_ep = anime_data.episodes;
     let mut anime_name = anime_data.title;
+    let mut rewatching = anime_data.rewatching;
     let mut mal_id = api::anilist::fetch::id_converter(&client, anime_id).await?;
+    // * Airing shows are capped at the last aired episode
+    let mut airing = api::anilist::fetch::data_by_id(client, anime_id).await?;
+    // * A picked episode is played even if it would be skipped
+    let (mut playing, skipped) = match picked {
+        Some(episode) => (episode, false),
+        None => {
+            let next_ep = player::next_episode(client, anime_id, mal_id, cur_ep + 1, &config).await?;
+            (Episode::from(next_ep), next_ep > cur_ep + 1)
+        }
+    };
+    if let Some(message) = playing.number().and_then(|episode| caught_up(&airing, episode)) {
+        println!("{}", message);
+        return Ok(());
+    }
 
     let mut cache: HashMap<u32, String> = default::Default::default();
+    // The anime that was finished without a sequel to continue with
+    let mut finished: Option<(i32, String)> = None;
+    // Shown once the player is closed and the screen is cleared
+    let mut notice: Option<String> = None;
 
     // Start initial player
-    player::start_watching(&client, anime_id, mal_id, cur_ep, &config, &anime_name).await?;
+    player::start_watching(client, anime_id, mal_id, &playing, &config, &anime_name, rewatching).await?;
+    if syncing && skipped && !playing.watched(progress) {
+        player::mark_skipped(client, anime_id, playing.progress(), &config).await?;
+    }
 
     // Main watching loop
     loop {
+        let syncing_episode = syncing && !playing.watched(progress);
         let binge = player::watching(
             &client,
             anime_id,
             mal_id,
-            cur_ep + 1,
+            &playing,
             max_ep,
             &config,
             &anime_name,
-            syncing,
+            syncing_episode,
+            rewatching,
             &mut rpc_client,
             &mut cache,
         )
         .await?;
 
         log::info!("Binge watching: {}", binge);
+        if syncing_episode {
+            progress = progress.max(playing.progress());
+        }
 
         // If this was the last episode of the series
         if binge {
-            cur_ep = cur_ep + 1;
+            // * After a special the next regular episode comes
+            cur_ep = playing.progress();
+            let mut ep_to_get = cur_ep + 1;
+            if cur_ep != max_ep {
+                ep_to_get = player::next_episode(client, anime_id, mal_id, ep_to_get, &config).await?;
+                // Every remaining episode of the season is skipped
+                if max_ep > 0 && ep_to_get > max_ep {
+                    log::info!("Skipped the rest of the season, from episode {}", cur_ep + 1);
+                    println!("The remaining episodes are skipped.");
+                    if syncing && progress < max_ep {
+                        progress = max_ep;
+                        api::anilist::mutation::update_progress(client, anime_id, max_ep).await?;
+                        api::anilist::list_entry::after_sync(client, anime_id, max_ep, max_ep, &config)
+                            .await;
+                    }
+                    cur_ep = max_ep;
+                } else if let Some(message) = caught_up(&airing, ep_to_get) {
+                    notice = Some(message);
+                    break;
+                } else if syncing && ep_to_get > cur_ep + 1 && ep_to_get > progress + 1 {
+                    player::mark_skipped(client, anime_id, ep_to_get, &config).await?;
+                }
+            }
+
             if cur_ep == max_ep {
                 // Handle scoring if enabled
                 if config.score_on_completion {
                     utils::clear();
-                    let theme = theme::CustomTheme {};
-                    let new_score: f64 = loop {
-                        let input: String = Input::with_theme(&theme)
-                            .with_prompt("Enter a score on a scale of 1 to 10")
-                            .interact_text()
-                            .expect("Failed to read input, or input was invalid");
-                        match input.parse::<f64>() {
-                            Ok(score) if score >= 1.0 && score <= 10.0 => break score,
-                            _ => {
-                                println!("Score must be between 1 and 10.");
-                                continue;
-                            }
-                        }
-                    };
-                    utils::clear();
-                    api::anilist::mutation::update_score(&client, anime_id, new_score).await?;
+                    api::anilist::score::ask_and_update(client, anime_id).await?;
                 }
 
                 println!("This was the last episode of the season.");
 
                 // Check for sequel
-                let sequel = sequel(&client, anime_id).await;
-                if let Ok(sequel_id) = sequel {
-                    // Update status for the sequel
-                    if config.score_on_completion {
-                        api::anilist::mutation::update_status(&client, sequel_id, 0).await?;
-                    } else {
-                        api::anilist::mutation::update_progress(&client, sequel_id, 0).await?;
+                let sequel = sequel(client, anime_id, rewatching).await;
+                if let Ok(part) = sequel {
+                    let sequel_id = part.id;
+                    // Update status for the sequel, a rewatch only carries on into a sequel that was completed
+                    let rewatch_sequel = rewatching && part.completed();
+                    if rewatch_sequel {
+                        api::anilist::mutation::update_status(client, sequel_id, api::anilist::mutation::REPEATING).await?;
+                        api::anilist::mutation::update_progress(client, sequel_id, 0).await?;
+                        let mut db = local_save::ProgressDatabase::load()
+                            .with_context(|| "Failed to load progress database")?;
+                        db.clear_rewatch(sequel_id);
+                        db.save().with_context(|| "Failed to save progress database")?;
+                    } else if config.auto_status_watching {
+                        api::anilist::mutation::update_status(client, sequel_id, api::anilist::mutation::CURRENT).await?;
+                    } else if part.list_entry.is_none() {
+                        api::anilist::mutation::update_progress(client, sequel_id, 0).await?;
                     }
+                    // * A sequel that was only started carries on from its progress
+                    let start = match &part.list_entry {
+                        Some(entry) if !rewatch_sequel => entry.progress.unwrap_or(0) + 1,
+                        _ => 1,
+                    };
+                    rewatching = rewatch_sequel;
+                    progress = start - 1;
 
                     // Update anime information for the sequel
                     anime_id = sequel_id;
-                    cur_ep = 0;
-                    let data = api::anilist::fetch::data_by_id(&client, anime_id).await?;
-                    max_ep = data.episodes;
-                    anime_name = data.title;
+                    airing = api::anilist::fetch::data_by_id(client, anime_id).await?;
+                    max_ep = airing.episodes;
+                    anime_name = airing.title.clone();
 
                     println!("Starting the sequel...");
                     mal_id = api::anilist::fetch::id_converter(&client, sequel_id).await?;
+                    let next_ep =
+                        player::next_episode(client, anime_id, mal_id, start, &config).await?;
+                    if syncing && next_ep > start {
+                        player::mark_skipped(client, anime_id, next_ep, &config).await?;
+                    }
+                    playing = Episode::from(next_ep);
+                    if let Some(message) = caught_up(&airing, next_ep) {
+                        notice = Some(message);
+                        break;
+                    }
 
-                    let client_clone = client.clone();
-                    let config_lang = config.language.clone();
-                    let config_quality = config.quality.clone();
-                    let config_sub_or_dub = config.sub_or_dub.clone();
-                    let name_clone = anime_name.clone();
-                    let config_clone = config.clone();
-                    let mut next_ep = cur_ep;
-                    tokio::task::spawn(async move {
-                        if skip_override::search(anime_id).filler {
-                            if !config_clone.skip_filler {
-                                next_ep = player::filler(&client_clone, mal_id, next_ep)
-                                    .await
-                                    .unwrap();
-                            }
-                        } else if config_clone.skip_filler {
-                            next_ep = player::filler(&client_clone, mal_id, next_ep)
-                                .await
-                                .unwrap();
-                        }
-
-                        let mut next_url: Result<String> = Err(anyhow::anyhow!(""));
-                        while next_url.is_err() {
-                            tokio::time::sleep(Duration::from_secs(3)).await;
-                            next_url = player::get_url(
-                                &client_clone,
-                                &config_lang,
-                                mal_id,
-                                anime_id,
-                                next_ep,
-                                &config_quality,
-                                &config_sub_or_dub,
-                                &name_clone,
-                            )
-                            .await
-                            .with_context(|| {
-                                format!("Failed to fetch URL for episode {}", next_ep)
-                            });
-                            if next_url.is_err() {
-                                eprintln!("Failed to get episode link, retrying...");
-                                log::warn!("Failed to get episode link for id: {}", anime_id);
-                            }
+                    let url =
+                        player::fetch_url(client, &config, mal_id, anime_id, &playing, &anime_name)
+                            .await;
+                    let loaded = match url {
+                        Ok(url) => mpvipc::send_command(&["loadfile", &url]).await.is_ok(),
+                        Err(e) => {
+                            log::warn!("{:#}", e);
+                            false
                         }
-
-                        mpvipc::send_command(&["loadfile", &next_url.unwrap()])
-                            .await
-                            .unwrap();
-                    });
+                    };
+                    if !loaded {
+                        log::warn!("Error loading the sequel");
+                        notice = Some("Failed to load next episode.".to_string());
+                        break;
+                    }
 
                     // Continue to the next iteration without exiting
                     continue;
                 } else {
-                    // No sequel, exit
+                    // No sequel, recommendations are offered once the player is done
+                    finished = Some((anime_id, anime_name.clone()));
                     break;
                 }
             }
 
-            let mut ep_to_get = cur_ep+1;
-            let override_setting = skip_override::search(anime_id);
-            if override_setting.filler {
-                if !config.skip_filler {
-                    ep_to_get = player::filler(&client, mal_id, ep_to_get).await?;
-                }
-            } else if config.skip_filler {
-                ep_to_get = player::filler(&client, mal_id, ep_to_get).await?;
-            }
+            // Skipped episodes are jumped over
+            playing = Episode::from(ep_to_get);
 
             let next_url = cache.get(&ep_to_get);
 
@@ -598,53 +784,22 @@ async fn watch(
                 }
                 None => {
                     println!("No link prefetched, fetching now.");
-                    let override_setting = skip_override::search(anime_id);
-
-                    if override_setting.filler {
-                        if !config.skip_filler {
-                            ep_to_get = player::filler(&client, mal_id, cur_ep).await?;
-                        }
-                    } else if config.skip_filler {
-                        cur_ep = player::filler(&client, mal_id, cur_ep).await?;
-                    }
-
-                    let mut url = player::get_url(
-                        &client,
-                        &config.language,
-                        mal_id,
-                        anime_id,
-                        ep_to_get,
-                        &config.quality,
-                        &config.sub_or_dub,
-                        &anime_name,
-                    )
-                    .await;
-
-                    while url.is_err() {
-                        sleep(Duration::from_secs(3)).await;
-                        url = player::get_url(
-                            &client,
-                            &config.language,
-                            mal_id,
-                            anime_id,
-                            cur_ep+1,
-                            &config.quality,
-                            &config.sub_or_dub,
-                            &anime_name,
-                        )
-                        .await;
-                    }
-
-                    match mpvipc::send_command(&["loadfile", &url?]).await {
-                        Ok(_) => {},
-                        Err(_) => {
-                            utils::clear();
-                            println!("Failed to load next episode.");
-                            log::warn!("Error loading next episode");
-                            tokio::time::sleep(Duration::from_secs(1)).await;
-                            break;
+                    let url =
+                        player::fetch_url(client, &config, mal_id, anime_id, &playing, &anime_name)
+                            .await;
+
+                    let loaded = match url {
+                        Ok(url) => mpvipc::send_command(&["loadfile", &url]).await.is_ok(),
+                        Err(e) => {
+                            log::warn!("{:#}", e);
+                            false
                         }
                     };
+                    if !loaded {
+                        log::warn!("Error loading next episode");
+                        notice = Some("Failed to load next episode.".to_string());
+                        break;
+                    }
                     log::debug!("Episode loaded");
                 }
             }
@@ -656,6 +811,12 @@ async fn watch(
     }
 
     utils::clear();
+    if let Some(notice) = notice {
+        println!("{}", notice);
+    }
+    if let Some((id, title)) = finished {
+        recommend::offer(client, id, &title).await?;
+    }
     println!("See you later!");
     if config.discord_presence {
         rpc_client.clear_activity().unwrap();
@@ -1,49 +1,44 @@
 // =============== Imports ================
 use crate::api;
-use crate::config;
+use crate::api::anilist::outbox::Delivery;
+use crate::config::{self, FillerPolicy};
 use crate::discord_rpc;
+use crate::episode::Episode;
+use crate::http;
 use crate::local_save;
 use crate::mpvipc;
 use crate::mpvipc::seek_to;
 use crate::scraping;
 use crate::skip_override;
+use crate::theme;
 use crate::utils;
 
 use anyhow::{Context, Result};
 use console::style;
+use dialoguer::Select;
 use discord_rpc_client;
 use reqwest::Client;
 use std::{collections::HashMap, path::Path, time::Duration};
+use tokio::task::JoinHandle;
 use tokio::time::sleep;
-use tokio::{self, sync::mpsc};
 
+// How many times getting an episode link is tried before giving up
+const URL_ATTEMPTS: u32 = 5;
+
+// Starts the player with the given episode
 pub async fn start_watching(
     client: &Client,
     id: i32,
     mal_id: i32,
-    progress: u32,
+    next_ep: &Episode,
     config: &config::Config,
     name: &String,
+    rewatching: bool,
 ) -> Result<()> {
-    let cur_ep = progress + 1;
-
-    let (tx, mut rx) = mpsc::channel(1);
-
     let client_clone = client.clone();
-    let config_lang = config.language.clone();
-    let config_quality = config.quality.clone();
-    let config_sub_or_dub = config.sub_or_dub.clone();
+    let config_clone = config.clone();
     let name_clone = name.clone();
-
-    let override_setting = skip_override::search(id);
-    let mut next_ep = cur_ep;
-    if override_setting.filler {
-        if !config.skip_filler {
-            next_ep = filler(&client, mal_id, next_ep).await?;
-        }
-    } else if config.skip_filler {
-        next_ep = filler(&client, mal_id, next_ep).await?;
-    }
+    let episode_clone = next_ep.clone();
 
     log::info!(
         "Starting playback for anime: {}, Episode: {}",
@@ -52,28 +47,8 @@ pub async fn start_watching(
     );
     println!("Loading - {}, episode: {}", name, next_ep);
 
-    tokio::task::spawn(async move {
-        let mut next_url: Result<String> = Err(anyhow::anyhow!(""));
-        while next_url.is_err() {
-            tokio::time::sleep(Duration::from_secs(3)).await;
-            next_url = get_url(
-                &client_clone,
-                &config_lang,
-                mal_id,
-                id,
-                next_ep,
-                &config_quality,
-                &config_sub_or_dub,
-                &name_clone,
-            )
-            .await
-            .with_context(|| format!("Failed to fetch URL for episode {}", next_ep));
-            if next_url.is_err() {
-                eprintln!("Failed to get episode link, retrying...");
-                log::warn!("Failed to get episode link for id: {}", id);
-            }
-        }
-        tx.send(next_url.unwrap()).await.unwrap();
+    let fetching = tokio::task::spawn(async move {
+        fetch_url(&client_clone, &config_clone, mal_id, id, &episode_clone, &name_clone).await
     });
 
     let mut player_args = config.player_args.split(' ').collect::<Vec<&str>>();
@@ -85,7 +60,9 @@ pub async fn start_watching(
     let program = &config.player;
     let ipc_socket = format!("--input-ipc-server={}", socket_path);
 
-    let url = rx.recv().await.unwrap();
+    let url = fetching
+        .await
+        .with_context(|| "Fetching the episode link was interrupted")??;
 
 
     let _ = std::process::Command::new(program)
@@ -108,18 +85,22 @@ pub async fn start_watching(
         Some(e) => e.to_owned(),
         None => local_save::WatchProgress {
             anilist_id: id,
-            episode: cur_ep,
+            episode: next_ep.clone(),
             position: 0.0,
             scraper_ids: {
                 let mut map = HashMap::new();
                 map.insert(config.language.to_string(), String::new());
                 map
             },
+            updated_at: 0,
+            rewatch: None,
         },
     };
 
-    if cur_ep == entry.episode {
-        let position = entry.position.round() as u64;
+    if let Some((episode, position)) = entry.position_for(rewatching)
+        && *next_ep == episode
+    {
+        let position = position.round() as u64;
         let resuming_text = format!(
             "{:02}:{:02}:{:02}",
             position / 3600,
@@ -139,7 +120,7 @@ pub async fn get_url(
     lang: &str,
     mal_id: i32,
     id: i32,
-    episode: u32,
+    episode: &Episode,
     quality: &str,
     sub_or_dub: &str,
     name: &String,
@@ -176,14 +157,85 @@ pub async fn get_url(
     Ok(url)
 }
 
-pub async fn filler(client: &Client, mal_id: i32, mut episode: u32) -> Result<u32> {
+// Gets the link of the episode, retrying a few times before giving up
+pub async fn fetch_url(
+    client: &Client,
+    config: &config::Config,
+    mal_id: i32,
+    id: i32,
+    episode: &Episode,
+    name: &String,
+) -> Result<String> {
+    let mut attempt = 0;
+    http::retry(URL_ATTEMPTS, || {
+        attempt += 1;
+        let retrying = attempt < URL_ATTEMPTS;
+        async move {
+            let url = get_url(
+                client,
+                &config.language,
+                mal_id,
+                id,
+                episode,
+                &config.quality,
+                &config.sub_or_dub,
+                name,
+            )
+            .await;
+            if url.is_err() {
+                if retrying {
+                    eprintln!("Failed to get episode link, retrying...");
+                }
+                log::warn!("Failed to get episode link for id: {}", id);
+            }
+            url
+        }
+    })
+    .await
+    .with_context(|| format!("Failed to fetch URL for episode {}", episode))
+}
+
+// Returns the first episode from `episode` onwards that isn't skipped
+// The whole season is fetched once from Jikan and cached, so this is cheap to call
+pub async fn skip_episodes(
+    client: &Client,
+    mal_id: i32,
+    mut episode: u32,
+    policy: FillerPolicy,
+    recaps: bool,
+) -> Result<u32> {
+    if !policy.skips() && !recaps {
+        return Ok(episode);
+    }
+
+    let episodes = match api::jikan::episodes(client, mal_id).await {
+        Ok(episodes) => episodes,
+        Err(e) => {
+            log::error!("Couldn't get filler data: {}", e);
+            eprintln!("Couldn't get filler data.");
+            return Ok(episode);
+        }
+    };
+
     loop {
-        let response = api::jikan::filler(&client, mal_id, episode).await;
-        if response.is_err() {
-            break;
-        } else if response.is_ok() && response? {
-            log::info!("Skipping episode {} because it's a filler", episode);
-            episode = episode + 1;
+        if recaps && episodes.is_recap(episode) {
+            log::info!("Skipping episode {} because it's a recap", episode);
+            episode += 1;
+        } else if policy.skips() && episodes.is_filler(episode) {
+            // Finding the end of the filler run
+            let mut last = episode;
+            while episodes.is_filler(last + 1) {
+                last += 1;
+            }
+
+            // Only asking at the start of a run, if the user is in the middle of one they chose to watch it
+            if policy == FillerPolicy::Ask
+                && ((episode > 1 && episodes.is_filler(episode - 1)) || !ask_filler(episode, last)?)
+            {
+                break;
+            }
+            log::info!("Skipping episodes {}-{} because they are fillers", episode, last);
+            episode = last + 1;
         } else {
             break;
         }
@@ -192,15 +244,74 @@ pub async fn filler(client: &Client, mal_id: i32, mut episode: u32) -> Result<u3
     Ok(episode)
 }
 
+fn ask_filler(first: u32, last: u32) -> Result<bool> {
+    utils::clear();
+    let prompt = if first == last {
+        format!("Episode {} is a filler. What would you like to do?", first)
+    } else {
+        format!("Episodes {}-{} are fillers. What would you like to do?", first, last)
+    };
+    let options = ["Skip", "Watch"];
+    let theme = theme::CustomTheme {};
+    let selection = Select::with_theme(&theme)
+        .with_prompt(prompt)
+        .items(&options)
+        .default(0)
+        .interact_opt()?;
+    utils::clear();
+
+    Ok(selection.unwrap_or(1) == 0)
+}
+
+// The filler policy and recap episode setting of the anime
+// Override basically does the opposite of the setting in the config file
+pub fn skip_settings(id: i32, config: &config::Config) -> (FillerPolicy, bool) {
+    let override_setting = skip_override::search(id);
+    let policy = override_setting.filler_policy(config);
+    let recaps = override_setting.recap_episodes != config.skip_recap_episodes;
+    (policy, recaps)
+}
+
+// Returns the next episode to play, skipping fillers and recap episodes if the config says so
+pub async fn next_episode(
+    client: &Client,
+    id: i32,
+    mal_id: i32,
+    episode: u32,
+    config: &config::Config,
+) -> Result<u32> {
+    let (policy, recaps) = skip_settings(id, config);
+    skip_episodes(client, mal_id, episode, policy, recaps).await
+}
+
+// Marks the skipped episodes as watched on AniList, if the filler policy says so
+// `skipped_to` is the episode that is played after skipping
+// ! Only "mark" does this right away, with "skip" and "ask" the progress stays before the skipped
+// ! episodes until the next one is synced, so quitting right after a skip leaves them unwatched
+pub async fn mark_skipped(
+    client: &Client,
+    id: i32,
+    skipped_to: u32,
+    config: &config::Config,
+) -> Result<()> {
+    let (policy, _) = skip_settings(id, config);
+    if policy == FillerPolicy::MarkWatched && skipped_to > 1 {
+        api::anilist::mutation::update_progress(client, id, skipped_to - 1).await?;
+        log::info!("Marked episodes before {} as watched", skipped_to);
+    }
+    Ok(())
+}
+
 pub async fn watching(
     client: &Client,
     id: i32,
     mal_id: i32,
-    cur_ep: u32,
+    cur_ep: &Episode,
     max_ep: u32,
     config: &config::Config,
     name: &String,
     syncing: bool,
+    rewatching: bool,
     rpc_client: &mut discord_rpc_client::Client,
     cache: &mut HashMap<u32, String>,
 ) -> Result<bool> {
@@ -211,28 +322,33 @@ pub async fn watching(
     let mut db = local_save::ProgressDatabase::load()?;
 
     let mut anime = api::aniskip::Anime {
-        episode: cur_ep,
+        episode: cur_ep.number().unwrap_or(0),
         mal_id: mal_id,
         skip_times: api::aniskip::SkipData::default(),
     };
 
-    let skip_times = api::aniskip::get_and_parse_ani_skip_data(
-        &client,
-        anime.mal_id,
-        anime.episode,
-        2,
-        &mut anime,
-    )
-    .await;
-
-    match skip_times {
-        Ok(_) => {
-            api::aniskip::send_skip_times_to_mpv(&anime)
-                .with_context(|| "Failed to send skip times to MPV")
-                .unwrap();
-        }
-        Err(e) => {
-            println!("Failed to fetch AniSkip data: {}", e);
+    // * AniSkip only knows the regular episodes
+    if cur_ep.is_special() {
+        log::info!("No AniSkip data for special episode {}", cur_ep);
+    } else {
+        let skip_times = api::aniskip::get_and_parse_ani_skip_data(
+            client,
+            anime.mal_id,
+            anime.episode,
+            2,
+            &mut anime,
+        )
+        .await;
+
+        match skip_times {
+            Ok(_) => {
+                api::aniskip::send_skip_times_to_mpv(&anime)
+                    .with_context(|| "Failed to send skip times to MPV")
+                    .unwrap();
+            }
+            Err(e) => {
+                println!("Failed to fetch AniSkip data: {}", e);
+            }
         }
     }
 
@@ -240,13 +356,26 @@ pub async fn watching(
         .await
         .with_context(|| "Failed to set properties")?;
 
+    let (filler_policy, _) = skip_settings(id, config);
+    println!(
+        "Watching - {}, episode: {} | Fillers: {}",
+        name,
+        cur_ep,
+        style(filler_policy).bold()
+    );
+
     let override_setting = skip_override::search(id);
 
     let mut time_pos: f64 = 0.0;
 
     let anime_data = api::anilist::fetch::data_by_id(&client, id).await?;
     if config.discord_presence {
-        let payload = discord_rpc::payload(&anime_data, cur_ep, max_ep, time_pos.round() as u64);
+        let payload = discord_rpc::payload(
+            &anime_data,
+            cur_ep.progress(),
+            anime_data.known_episodes(),
+            time_pos.round() as u64,
+        );
         rpc_client
             .set_activity(|_| payload)
             .expect("Failed to update activity");
@@ -257,12 +386,14 @@ pub async fn watching(
         sleep(Duration::from_millis(250)).await;
     }
     let duration = mpvipc::get_property("duration").await?;
-    let mut caching = false;
-    let (tx, mut rx) = mpsc::channel(1);
-
-    let entry = db.get_entry(id).unwrap();
-    if entry.episode == cur_ep {
-        seek_to(entry.position).await?
+    let mut prefetching: Option<JoinHandle<Result<String>>> = None;
+    // * After a special the next regular episode comes
+    let mut prefetched_ep = cur_ep.progress() + 1;
+
+    if let Some((episode, position)) = db.get_entry(id).and_then(|e| e.position_for(rewatching))
+        && episode == *cur_ep
+    {
+        seek_to(position).await?
     }
 
     let end: bool;
@@ -287,55 +418,33 @@ pub async fn watching(
 
         let percent = time_pos / duration * 100.0;
 
-        if percent > 70.0 && !caching && max_ep != cur_ep {
-            caching = true;
+        if percent > 70.0
+            && prefetching.is_none()
+            && max_ep != cur_ep.progress()
+            && anime_data.has_aired(cur_ep.progress() + 1)
+        {
             println!("Prefetching next episode.");
-            
+
             let client_copy = client.clone();
             let config_copy = config.clone();
             let name_copy = name.clone();
-            let tx = tx.clone();
-            let mut next_ep = cur_ep + 1;
-
-            if override_setting.filler {
-                if !config.skip_filler {
-                    next_ep = filler(&client, mal_id, next_ep).await?;
-                }
-            } else if config.skip_filler {
-                next_ep = filler(&client, mal_id, next_ep).await?;
+            // Can't ask in the middle of an episode, so the question is saved for later
+            let (mut policy, recaps) = skip_settings(id, config);
+            if policy == FillerPolicy::Ask {
+                policy = FillerPolicy::Play;
             }
-
-            tokio::task::spawn(async move {
-                let mut url = get_url(
-                    &client_copy,
-                    &config_copy.language,
-                    mal_id,
-                    id,
-                    next_ep,
-                    &config_copy.quality,
-                    &config_copy.sub_or_dub,
-                    &name_copy,
-                )
-                .await;
-
-                while url.is_err() {
-                    sleep(Duration::from_secs(3)).await;
-                    url = get_url(
-                        &client_copy,
-                        &config_copy.language,
-                        mal_id,
-                        id,
-                        next_ep,
-                        &config_copy.quality,
-                        &config_copy.sub_or_dub,
-                        &name_copy,
-                    )
-                    .await;
+            let next_ep = skip_episodes(client, mal_id, cur_ep.progress() + 1, policy, recaps).await?;
+            prefetched_ep = next_ep;
+            let next_ep = Episode::from(next_ep);
+
+            prefetching = Some(tokio::task::spawn(async move {
+                let url =
+                    fetch_url(&client_copy, &config_copy, mal_id, id, &next_ep, &name_copy).await;
+                if url.is_ok() {
+                    println!("Next episode successfully fetched.");
                 }
-                tx.send(url.unwrap()).await.unwrap();
-
-                println!("Next episode successfully fetched.");
-            });
+                url
+            }));
         }
 
         // Skipping intro and outro
@@ -431,27 +540,47 @@ pub async fn watching(
             .map(|s| s.to_string())
             .unwrap();
 
-        db.update_or_add(id, cur_ep, time_pos, &config.language, &scraper_id);
+        let completed = time_pos / duration * 100.0 >= config.completion_time as f64;
+        db.save_position(id, rewatching, cur_ep, time_pos, &config.language, &scraper_id);
+        // * The rewatch is over, the next one starts from scratch
+        if rewatching && completed && max_ep > 0 && cur_ep.number().is_some_and(|ep| ep >= max_ep) {
+            db.clear_rewatch(id);
+        }
         db.save()
             .with_context(|| "Failed to save progress database")?;
 
-        if time_pos / duration * 100.0 >= config.completion_time as f64 {
-            api::anilist::mutation::update_progress(&client, id, cur_ep).await?;
-            log::info!("Synced to anilist\n");
-            println!("Synced to anilist");
+        // * Specials are only kept locally, the AniList progress counts regular episodes
+        if completed && let Some(episode) = cur_ep.number() {
+            let delivery = api::anilist::mutation::update_progress(client, id, episode).await?;
+            if delivery == Delivery::Sent {
+                log::info!("Synced to anilist\n");
+                println!("Synced to anilist");
+            }
+            api::anilist::list_entry::after_sync(client, id, episode, max_ep, config).await;
         }
         log::info!("Saved progress for episode: {}", cur_ep);
     }
 
-    if caching {
-        cache.insert(cur_ep + 1, rx.recv().await.unwrap());
+    // The prefetched link is only needed if the next episode is coming
+    if let Some(task) = prefetching {
+        if end {
+            match task.await {
+                Ok(Ok(url)) => {
+                    cache.insert(prefetched_ep, url);
+                }
+                Ok(Err(e)) => log::warn!("Prefetching failed: {:#}", e),
+                Err(e) => log::warn!("Prefetching was interrupted: {}", e),
+            }
+        } else {
+            task.abort();
+        }
     }
     log::info!("Playback stopped for Episode: {} at {}", cur_ep, time_pos);
 
     Ok(end)
 }
 
-async fn update_mpv_properties(name: &str, cur_ep: u32) -> Result<()> {
+async fn update_mpv_properties(name: &str, cur_ep: &Episode) -> Result<()> {
     let title = format!("{} - Episode {}", name, cur_ep);
     mpvipc::set_property("title", &title).await?;
     mpvipc::set_property("force-media-title", &title).await?;
@@ -0,0 +1,137 @@
+// Named profiles, so more people can use yato on the same machine
+// Every profile has its own AniList login, progress and overrides, and optionally its own config
+// The "default" profile uses the old locations, so nothing has to be moved
+
+// =============== Imports ================
+use crate::theme;
+use crate::utils;
+
+use anyhow::{Context, Result};
+use dialoguer::Select;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+// * Set once at startup, before anything reads or writes user data
+static ACTIVE: OnceLock<String> = OnceLock::new();
+
+// Picks the profile from the flag, or asks if there is more than one
+pub fn init(requested: Option<&str>) -> Result<()> {
+    let name = match requested {
+        Some(name) => {
+            validate(name)?;
+            if !list().iter().any(|p| p == name) {
+                println!("Created new profile: {}", name);
+                log::info!("Creating profile: {}", name);
+            }
+            name.to_string()
+        }
+        None => {
+            let profiles = list();
+            if profiles.len() > 1 {
+                select(&profiles)?
+            } else {
+                DEFAULT_PROFILE.to_string()
+            }
+        }
+    };
+
+    log::info!("Using profile: {}", name);
+    ACTIVE
+        .set(name)
+        .map_err(|_| anyhow::anyhow!("Profile was already chosen"))?;
+    data_dir()?; // Making sure the directory exists
+    Ok(())
+}
+
+pub fn active() -> &'static str {
+    ACTIVE.get().map(String::as_str).unwrap_or(DEFAULT_PROFILE)
+}
+
+pub fn is_default() -> bool {
+    active() == DEFAULT_PROFILE
+}
+
+// Where the profile keeps its token, user id, progress and overrides
+pub fn data_dir() -> Result<PathBuf> {
+    let mut path = dirs::data_local_dir()
+        .ok_or_else(|| anyhow::anyhow!("Failed to get local data directory"))?
+        .join("yato");
+    if !is_default() {
+        path = path.join("profiles").join(active());
+    }
+    fs::create_dir_all(&path)
+        .with_context(|| format!("Failed to create profile directory: {:?}", path))?;
+    Ok(path)
+}
+
+// The profile's own config if it has one, the shared one otherwise
+pub fn config_file() -> PathBuf {
+    let config_dir = dirs::config_dir()
+        .expect("Could not find config directory. Please set the XDG_CONFIG_HOME environment variable.")
+        .join("yato");
+    if !is_default() {
+        let own = profile_config_file(&config_dir);
+        if own.exists() {
+            return own;
+        }
+    }
+    config_dir.join("yato.conf")
+}
+
+// ~/.config/yato/profiles/<name>.conf
+fn profile_config_file(config_dir: &Path) -> PathBuf {
+    config_dir.join("profiles").join(format!("{}.conf", active()))
+}
+
+// Every profile that has a data directory, default first
+pub fn list() -> Vec<String> {
+    let mut profiles = vec![DEFAULT_PROFILE.to_string()];
+    let Some(dir) = dirs::data_local_dir().map(|d| d.join("yato/profiles")) else {
+        return profiles;
+    };
+    if let Ok(entries) = fs::read_dir(dir) {
+        let mut names: Vec<String> = entries
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_dir())
+            .filter_map(|e| e.file_name().into_string().ok())
+            .filter(|name| name != DEFAULT_PROFILE)
+            .collect();
+        names.sort();
+        profiles.extend(names);
+    }
+    profiles
+}
+
+fn select(profiles: &[String]) -> Result<String> {
+    let theme = theme::CustomTheme {};
+    let selection = Select::with_theme(&theme)
+        .with_prompt("Choose a profile:")
+        .items(profiles)
+        .default(0)
+        .interact_opt()?;
+    utils::clear();
+    match selection {
+        Some(index) => Ok(profiles[index].clone()),
+        None => Err(anyhow::anyhow!("No profile was selected")),
+    }
+}
+
+// Profile names end up in paths, so only simple names are allowed
+fn validate(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 32
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "Invalid profile name: {} (use letters, numbers, - and _)",
+            name
+        ))
+    }
+}
@@ -0,0 +1,118 @@
+// Recommendations from AniList users
+// Offered after finishing a show, and collected from the best rated completed anime in a menu
+
+// =============== Imports ================
+use crate::api::anilist::fetch::{self, Recommendation};
+use crate::api::anilist::{list_entry, user_fetch};
+use crate::cover;
+use crate::theme;
+use crate::utils;
+
+use anyhow::Result;
+use dialoguer::Confirm;
+use futures::future::join_all;
+use reqwest::Client;
+
+// How many of the best rated completed anime are asked for recommendations
+const TOP_RATED: usize = 10;
+
+fn row(recommendation: &Recommendation, because: &[String]) -> String {
+    let score = utils::score(recommendation.average_score);
+    let mut row = format!(
+        "{} | {} | {}",
+        recommendation.title,
+        recommendation.format.as_deref().unwrap_or("?"),
+        score
+    );
+    if !because.is_empty() {
+        row.push_str(&format!(" | because you watched {}", because.join(", ")));
+    }
+    row
+}
+
+// Lets the user pick recommendations to add until they back out
+async fn pick(client: &Client, prompt: &str, mut items: Vec<(Recommendation, Vec<String>)>) -> Result<()> {
+    while !items.is_empty() {
+        let rows: Vec<String> = items.iter().map(|(r, because)| row(r, because)).collect();
+        let covers: Vec<Option<String>> = items.iter().map(|(r, _)| r.cover.clone()).collect();
+        let selection = cover::select(client, prompt, &rows, &covers, 0).await?;
+        utils::clear();
+        let Some(index) = selection else {
+            return Ok(());
+        };
+        let (recommendation, _) = items.remove(index);
+        list_entry::ask_and_add(client, recommendation.id, &recommendation.title).await?;
+    }
+    Ok(())
+}
+
+// Asks if the user wants to see what people recommend after the anime they just finished
+pub async fn offer(client: &Client, media_id: i32, title: &str) -> Result<()> {
+    let recommendations = match fetch::recommendations(client, media_id).await {
+        Ok(recommendations) => recommendations,
+        Err(e) => {
+            log::warn!("No recommendations for {}: {:#}", media_id, e);
+            return Ok(());
+        }
+    };
+    if recommendations.is_empty() {
+        return Ok(());
+    }
+
+    let theme = theme::CustomTheme {};
+    let show = Confirm::with_theme(&theme)
+        .with_prompt(format!(
+            "See {} recommendations for {}?",
+            recommendations.len(),
+            title
+        ))
+        .default(true)
+        .interact_opt()?
+        .unwrap_or(false);
+    utils::clear();
+    if !show {
+        return Ok(());
+    }
+
+    let items = recommendations.into_iter().map(|r| (r, Vec::new())).collect();
+    pick(client, &format!("Because you watched {}:", title), items).await
+}
+
+// Recommendations of the best rated completed anime, the ones recommended the most first
+pub async fn menu(client: &Client) -> Result<()> {
+    let top = user_fetch::top_rated(client, TOP_RATED).await?;
+    if top.is_empty() {
+        println!("Score some completed anime to get recommendations.");
+        return Ok(());
+    }
+
+    let results = join_all(top.iter().map(|(id, _)| fetch::recommendations(client, *id))).await;
+
+    // Recommendation -> summed rating and the anime that recommended it
+    let mut items: Vec<(Recommendation, Vec<String>)> = Vec::new();
+    for ((_, source), result) in top.iter().zip(results) {
+        let recommendations = match result {
+            Ok(recommendations) => recommendations,
+            Err(e) => {
+                log::warn!("No recommendations for {}: {:#}", source, e);
+                continue;
+            }
+        };
+        for recommendation in recommendations {
+            match items.iter_mut().find(|(r, _)| r.id == recommendation.id) {
+                Some((existing, because)) => {
+                    existing.rating += recommendation.rating;
+                    because.push(source.clone());
+                }
+                None => items.push((recommendation, vec![source.clone()])),
+            }
+        }
+    }
+
+    if items.is_empty() {
+        println!("No new recommendations, you've seen them all.");
+        return Ok(());
+    }
+    items.sort_by_key(|(r, because)| std::cmp::Reverse((because.len(), r.rating)));
+    pick(client, "Recommended for you:", items).await
+}
@@ -4,11 +4,17 @@
 #![allow(unused)] // * There are some unused variables in structs that are just needed for deserialization
 
 // =============== Imports ================
+use crate::api::anilist::fetch;
+use crate::api::anilist::graphql::Title;
+use crate::config::TitleLanguage;
+use crate::episode;
+use crate::http::{self, Service};
 use crate::local_save::ProgressDatabase;
 use crate::{theme, utils};
 
 use anyhow::{Context, Result};
 use dialoguer::FuzzySelect;
+use futures::future::join_all;
 use regex::Regex;
 use reqwest::{header::{REFERER, USER_AGENT}, Client};
 use serde::Deserialize;
@@ -25,7 +31,7 @@ pub async fn get_link(
     client: &Client,
     language: &str,
     anilist_id: i32,
-    episode: u32,
+    episode: &episode::Episode,
     quality: &str,
     sub_or_dub: &str,
     name: &str,
@@ -36,21 +42,8 @@ pub async fn get_link(
         episode
     );
 
-    // Load progress database
-    let mut db = ProgressDatabase::load().with_context(|| "Failed to load progress database")?;
-
-    // Try to reuse scraper_id if it exists
-    let scraper_id = db.get_scraper_id(anilist_id, language);
-    if scraper_id.is_none() {
-        log::info!("Scraper ID not found in database, scraping new one...");
-        let id = get_scraper_id(&client, name, sub_or_dub)
-            .await
-            .with_context(|| format!("Failed to scrape scraper ID for name: {}", name))?;
-
-        db.update_or_add(anilist_id, episode, 0.0, language, &id);
-        db.save().ok();
-    }
-    let scraper_id = db.get_scraper_id(anilist_id, language).unwrap();
+    let scraper_id = scraper_id(client, language, anilist_id, sub_or_dub, name).await?;
+    let scraper_id = scraper_id.as_str();
 
     let available_episodes = episodes_list(&client, scraper_id, sub_or_dub).await?;
 
@@ -58,7 +51,7 @@ pub async fn get_link(
         eprintln!("No episodes found for the given anime.");
         println!("Please try again later.");
         return Err(anyhow::anyhow!("No episodes found"));
-    } else if !available_episodes.contains(&episode.to_string()) {
+    } else if !available_episodes.contains(episode) {
         eprintln!(
             "Episode {} not found in the list of available episodes.",
             episode
@@ -79,6 +72,49 @@ pub async fn get_link(
     Ok(url)
 }
 
+// Episodes AllAnime has of the anime, in order
+pub async fn available_episodes(
+    client: &Client,
+    language: &str,
+    anilist_id: i32,
+    sub_or_dub: &str,
+    name: &str,
+) -> Result<Vec<episode::Episode>> {
+    let scraper_id = scraper_id(client, language, anilist_id, sub_or_dub, name).await?;
+    episodes_list(client, &scraper_id, sub_or_dub).await
+}
+
+// Reuses the scraper id from the progress database, or searches AllAnime for it
+async fn scraper_id(
+    client: &Client,
+    language: &str,
+    anilist_id: i32,
+    sub_or_dub: &str,
+    name: &str,
+) -> Result<String> {
+    let mut db = ProgressDatabase::load().with_context(|| "Failed to load progress database")?;
+    if let Some(id) = db.get_scraper_id(anilist_id, language).filter(|id| !id.is_empty()) {
+        return Ok(id.to_string());
+    }
+
+    log::info!("Scraper ID not found in database, scraping new one...");
+    // * AllAnime might know the anime by any of its names, so all of them are searched
+    let names = match fetch::titles(client, anilist_id).await {
+        Ok(names) => names,
+        Err(e) => {
+            log::warn!("Searching AllAnime only for {}: {:#}", name, e);
+            vec![name.to_string()]
+        }
+    };
+    let id = get_scraper_id(client, &names, sub_or_dub)
+        .await
+        .with_context(|| format!("Failed to scrape scraper ID for name: {}", name))?;
+
+    db.set_scraper_id(anilist_id, language, &id);
+    db.save().ok();
+    Ok(id)
+}
+
 // ================ Search for AllAnime ID ================
 #[derive(Deserialize)]
 struct GraphQLResponse {
@@ -108,7 +144,56 @@ struct AnimeShow {
     typename: String,
 }
 
-async fn get_scraper_id(client: &Client, name: &str, mode: &str) -> Result<String> {
+// Searches AllAnime with every name, results of the first names come first
+async fn get_scraper_id(client: &Client, names: &[String], mode: &str) -> Result<String> {
+    let searches = join_all(names.iter().map(|name| search_shows(client, name, mode))).await;
+
+    let mut results: Vec<(String, String)> = vec![];
+    for (name, search) in names.iter().zip(searches) {
+        let shows = match search {
+            Ok(shows) => shows,
+            Err(e) => {
+                log::warn!("AllAnime search for {} failed: {:#}", name, e);
+                continue;
+            }
+        };
+        for anime in shows {
+            if results.iter().any(|(id, _)| *id == anime.id) {
+                continue;
+            }
+            // * AllAnime's name is the romaji one
+            let display_name = match Title::language() {
+                TitleLanguage::English => anime.english_name.clone().unwrap_or(anime.name.clone()),
+                _ => anime.name.clone(),
+            };
+            results.push((anime.id, display_name));
+        }
+    }
+    if results.is_empty() {
+        return Err(anyhow::anyhow!("No anime found on AllAnime for: {}", names.join(", ")));
+    }
+
+    let titles: Vec<String> = results.iter().map(|(_, title)| title.clone()).collect();
+
+    // Letting the user select the correct anime
+    let theme = theme::CustomTheme {};
+    let selection = FuzzySelect::with_theme(&theme)
+        .with_prompt("Choose the correct anime:")
+        .items(&titles)
+        .default(0)
+        .interact_opt()
+        .expect("Failed to select anime");
+    utils::clear();
+
+    if selection.is_none() {
+        return Err(anyhow::anyhow!("No selection was made"))
+    }
+    let (anime_id, _anime_name) = results[selection.unwrap()].clone();
+
+    Ok(anime_id)
+}
+
+async fn search_shows(client: &Client, name: &str, mode: &str) -> Result<Vec<AnimeShow>> {
     let query = r#"
         query($search: SearchInput, $limit: Int, $page: Int, $translationType: VaildTranslationTypeEnumType, $countryOrigin: VaildCountryOriginEnumType) {
             shows(search: $search, limit: $limit, page: $page, translationType: $translationType, countryOrigin: $countryOrigin) {
@@ -143,43 +228,19 @@ async fn get_scraper_id(client: &Client, name: &str, mode: &str) -> Result<Strin
         ALLANIME_API, query_encoded, variables_encoded
     );
 
-    let response = client
+    let request = client
         .get(&url)
         .header(USER_AGENT, AGENT)
-        .header(REFERER, ALLANIME_REF)
-        .send()
+        .header(REFERER, ALLANIME_REF);
+    let response = http::send(Service::AllAnime, request)
         .await
         .with_context(|| format!("Failed to send request to AllAnime API: {}", url))?;
 
-    let body = response.text().await.expect("Failed to read response");
+    let body = response.text().await.with_context(|| "Failed to read AllAnime response")?;
     let parsed: GraphQLResponse = serde_json::from_str(&body)
         .with_context(|| "Failed to parse JSON response from AllAnime API")?;
 
-    let mut results = vec![];
-
-    for anime in parsed.data.shows.edges {
-        let display_name = anime.english_name.clone().unwrap_or(anime.name.clone());
-        results.push((anime.id, display_name));
-    }
-
-    let titles: Vec<String> = results.iter().map(|(_, title)| title.clone()).collect();
-
-    // Letting the user select the correct anime
-    let theme = theme::CustomTheme {};
-    let selection = FuzzySelect::with_theme(&theme)
-        .with_prompt("Choose the correct anime:")
-        .items(&titles)
-        .default(0)
-        .interact_opt()
-        .expect("Failed to select anime");
-    utils::clear();
-
-    if selection.is_none() {
-        return Err(anyhow::anyhow!("No selection was made"))
-    }
-    let (anime_id, _anime_name) = results[selection.unwrap()].clone();
-
-    Ok(anime_id)
+    Ok(parsed.data.shows.edges)
 }
 
 // ================ Fetch episode list ===============
@@ -206,7 +267,7 @@ async fn episodes_list(
     client: &Client, 
     allanime_id: &str, 
     mode: &str
-) -> Result<Vec<String>> {
+) -> Result<Vec<episode::Episode>> {
     let query =
         r#"query ($showId: String!) {
         show( _id: $showId ) {
@@ -222,11 +283,11 @@ async fn episodes_list(
         ALLANIME_API, encoded_variables, encoded_query
     );
 
-    let response = client
+    let request = client
         .get(&url)
         .header(USER_AGENT, AGENT)
-        .header(REFERER, ALLANIME_REF)
-        .send()
+        .header(REFERER, ALLANIME_REF);
+    let response = http::send(Service::AllAnime, request)
         .await
         .with_context(|| format!("Failed to send request to fetch episode list: {}", url))?
         .text()
@@ -244,8 +305,8 @@ async fn episodes_list(
 fn extract_episodes(
     available_episodes_detail: &HashMap<String, serde_json::Value>,
     mode: &str,
-) -> Vec<String> {
-    let mut episodes = vec![];
+) -> Vec<episode::Episode> {
+    let mut list = vec![];
 
     if let Some(eps) = available_episodes_detail
         .get(mode)
@@ -253,18 +314,14 @@ fn extract_episodes(
     {
         for ep in eps {
             if let Some(ep_str) = ep.as_str() {
-                episodes.push(ep_str.to_string());
+                // * "6.5" recaps and "0" prologues are kept too
+                list.push(episode::Episode::new(ep_str));
             }
         }
     }
 
-    episodes.sort_by(|a, b| {
-        let a_num = a.parse::<f64>().unwrap_or(0.0);
-        let b_num = b.parse::<f64>().unwrap_or(0.0);
-        a_num.partial_cmp(&b_num).unwrap()
-    });
-
-    episodes
+    list.sort();
+    list
 }
 
 // ================ Fetch video links ===============
@@ -300,11 +357,11 @@ fn decode_provider_id(encoded: &str) -> String {
 
 async fn extract_links(client: &Client, provider_id: &str) -> Result<Value> {
     let url = format!("https://allanime.day{}", provider_id);
-    let res = client
+    let request = client
         .get(url)
         .header(REFERER, ALLANIME_REF)
-        .header(USER_AGENT, AGENT)
-        .send().await.with_context(|| {
+        .header(USER_AGENT, AGENT);
+    let res = http::send(Service::AllAnime, request).await.with_context(|| {
             format!("Failed to send request to fetch links from provider ID: {}", provider_id)
         })?;
 
@@ -340,7 +397,7 @@ struct SourceUrl {
 async fn get_episode_url(
     client: &Client,
     show_id: &str,
-    ep_no: u32,
+    ep_no: &episode::Episode,
     translation_type: &str,
 ) -> Result<Vec<String>> {
     let query = r#"
@@ -363,11 +420,11 @@ async fn get_episode_url(
         urlencoding::encode(&variables.to_string())
     );
 
-    let resp = client
+    let request = client
         .get(&url)
         .header(USER_AGENT, AGENT)
-        .header(REFERER, ALLANIME_REF)
-        .send().await
+        .header(REFERER, ALLANIME_REF);
+    let resp = http::send(Service::AllAnime, request).await
         .with_context(|| format!("Failed to send request to fetch episode URLs: {}", url))?;
 
     let json_resp: EpisodeResponse = resp.json().await
@@ -403,7 +460,7 @@ async fn get_episode_url(
 async fn get_video_link(
     client: &Client,
     show_id: &str,
-    ep_no: u32,
+    ep_no: &episode::Episode,
     quality: &str,
     translation_type: &str,
 ) -> Result<String> {
@@ -512,7 +569,7 @@ fn get_priority_link(priorities: Vec<&str>, links: Vec<String>) -> Option<String
 
 async fn get_resolution_link(client: &Client, m3u8_url: &str, target_resolution: &str) -> Result<String> {
     // Fetch the m3u8 content
-    let response = client.get(m3u8_url).send().await?;
+    let response = http::send(Service::Other, client.get(m3u8_url)).await?;
     let m3u8_content = response.bytes().await?;
     
     // Parse the playlist
@@ -2,6 +2,8 @@
 // The code is made by me completely
 
 // =============== Imports ================
+use crate::episode::Episode;
+use crate::http::{self, Service};
 use crate::local_save::ProgressDatabase;
 
 use regex::Regex;
@@ -13,7 +15,7 @@ pub async fn get_link(
     client: &Client,
     mal_id: i32,
     anilist_id: i32,
-    ep: u32,
+    ep: &Episode,
     quality: &str,
 ) -> Result<String> {
     log::info!("Fetching link for MAL ID: {}, Episode: {}", mal_id, ep);
@@ -38,7 +40,7 @@ pub async fn get_link(
             .with_context(|| format!("Failed to scrape scraper ID for MAL ID: {}", mal_id))?;
 
         // Update or add the entry with the correct scraper_id for the language
-        db.update_or_add(anilist_id, ep, 0.0, language, &id);
+        db.set_scraper_id(anilist_id, language, &id);
         db.save().ok();
     }
     let scraper_id = db.get_scraper_id(anilist_id, language).unwrap();
@@ -71,7 +73,7 @@ async fn get_scraper_id(client: &Client, mal_id: i32) -> Result<String> {
     log::info!("Searching for AnimeDrive ID with MAL link: {}", mal_link);
     let search_url = format!("https://animedrive.hu/search/?q={}", mal_link);
 
-    let response = client.get(&search_url).send().await
+    let response = http::send(Service::AnimeDrive, client.get(&search_url)).await
         .with_context(|| format!("Failed to send request to AnimeDrive: {}", search_url))?;
     let final_url = response.url().to_string();
 
@@ -88,7 +90,7 @@ async fn get_scraper_id(client: &Client, mal_id: i32) -> Result<String> {
 }
 
 async fn get_html(client: &Client, player_link: &str) -> Result<String> {
-    let response = client.get(player_link).send().await
+    let response = http::send(Service::AnimeDrive, client.get(player_link)).await
         .with_context(|| format!("Failed to send request to player link: {}", player_link))?;
     Ok(response.text().await?)
 }
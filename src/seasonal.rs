@@ -0,0 +1,173 @@
+// Browser for the anime of the current season
+// Shows can be sorted and filtered, then added to the list or watched right away
+
+// =============== Imports ================
+use crate::api::anilist::fetch::{self, SeasonMedia};
+use crate::api::anilist::{list_entry, mutation, user_fetch};
+use crate::cover;
+use crate::theme;
+use crate::utils;
+
+use anyhow::Result;
+use chrono::{Datelike, Local};
+use dialoguer::Select;
+use reqwest::Client;
+use std::cmp::Reverse;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Sort {
+    Popularity,
+    Score,
+    StartDate,
+}
+
+impl Sort {
+    const ALL: [Sort; 3] = [Sort::Popularity, Sort::Score, Sort::StartDate];
+
+    fn name(self) -> &'static str {
+        match self {
+            Sort::Popularity => "Popularity",
+            Sort::Score => "Score",
+            Sort::StartDate => "Start date",
+        }
+    }
+
+    fn apply(self, media: &mut [SeasonMedia]) {
+        match self {
+            Sort::Popularity => media.sort_by_key(|m| Reverse(m.popularity.unwrap_or(0))),
+            Sort::Score => media.sort_by_key(|m| Reverse(m.average_score.unwrap_or(0))),
+            Sort::StartDate => media.sort_by_key(|m| {
+                // * Shows without a date go last
+                let date = &m.start_date;
+                (date.year.is_none(), date.year, date.month, date.day)
+            }),
+        }
+    }
+}
+
+// AniList season, its name and the year of today
+fn current_season() -> (&'static str, &'static str, i32) {
+    let today = Local::now();
+    let (season, name) = match today.month() {
+        1..=3 => ("WINTER", "Winter"),
+        4..=6 => ("SPRING", "Spring"),
+        7..=9 => ("SUMMER", "Summer"),
+        _ => ("FALL", "Fall"),
+    };
+    (season, name, today.year())
+}
+
+fn row(media: &SeasonMedia) -> String {
+    let score = utils::score(media.average_score);
+    let list = media
+        .media_list_entry
+        .as_ref()
+        .and_then(|e| e.status.as_deref())
+        .map(|status| format!(" [{}]", mutation::status_label(status)))
+        .unwrap_or_default();
+    format!(
+        "{} | {} | {} | {}{}",
+        media.title.preferred(),
+        media.format.as_deref().unwrap_or("?"),
+        score,
+        media.start_date,
+        list
+    )
+}
+
+// Returns the anime to watch, if the user chose to watch one
+pub async fn browse(client: &Client) -> Result<Option<user_fetch::AnimeData>> {
+    let (season, season_name, year) = current_season();
+    let all = fetch::season(client, season, year).await?;
+    if all.is_empty() {
+        println!("No anime found for this season.");
+        return Ok(None);
+    }
+
+    let mut formats: Vec<String> = all.iter().filter_map(|m| m.format.clone()).collect();
+    formats.sort();
+    formats.dedup();
+    let mut genres: Vec<String> = all.iter().flat_map(|m| m.genres.clone()).collect();
+    genres.sort();
+    genres.dedup();
+
+    let mut sort = Sort::Popularity;
+    let mut format: Option<String> = None;
+    let mut genre: Option<String> = None;
+    let theme = theme::CustomTheme {};
+
+    loop {
+        let mut shown: Vec<SeasonMedia> = all
+            .iter()
+            .filter(|m| format.is_none() || m.format == format)
+            .filter(|m| genre.as_ref().is_none_or(|g| m.genres.contains(g)))
+            .cloned()
+            .collect();
+        sort.apply(&mut shown);
+
+        let options = vec![
+            format!("Browse ({} anime)", shown.len()),
+            format!("Sort by: {}", sort.name()),
+            format!("Format: {}", format.as_deref().unwrap_or("Any")),
+            format!("Genre: {}", genre.as_deref().unwrap_or("Any")),
+            "Back".to_string(),
+        ];
+        let selection = Select::with_theme(&theme)
+            .with_prompt(format!("{} {}", season_name, year))
+            .items(&options)
+            .default(0)
+            .interact_opt()?;
+        utils::clear();
+
+        match selection {
+            Some(0) => {
+                if shown.is_empty() {
+                    println!("Nothing matches the filters.");
+                    continue;
+                }
+                let rows: Vec<String> = shown.iter().map(row).collect();
+                let covers: Vec<Option<String>> = shown
+                    .iter()
+                    .map(|m| m.cover_image.clone().and_then(|c| c.medium))
+                    .collect();
+                let picked = cover::select(client, "Choose an anime:", &rows, &covers, 0).await?;
+                utils::clear();
+                if let Some(index) = picked
+                    && let Some(data) = list_entry::watch_or_add(
+                        client,
+                        shown[index].id,
+                        &shown[index].title.preferred(),
+                        shown[index].episodes,
+                        shown[index].media_list_entry.as_ref(),
+                    )
+                    .await?
+                {
+                    return Ok(Some(data));
+                }
+            }
+            Some(1) => {
+                let names: Vec<&str> = Sort::ALL.iter().map(|s| s.name()).collect();
+                let picked = Select::with_theme(&theme)
+                    .with_prompt("Sort by")
+                    .items(&names)
+                    .default(0)
+                    .interact_opt()?;
+                utils::clear();
+                if let Some(index) = picked {
+                    sort = Sort::ALL[index];
+                }
+            }
+            Some(2) => {
+                if let Some(picked) = utils::pick("Format", &formats)? {
+                    format = picked;
+                }
+            }
+            Some(3) => {
+                if let Some(picked) = utils::pick("Genre", &genres)? {
+                    genre = picked;
+                }
+            }
+            _ => return Ok(None),
+        }
+    }
+}
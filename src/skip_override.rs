@@ -1,5 +1,7 @@
 // =============== Imports ================
 use crate::api::anilist::fetch;
+use crate::config::{self, Config, FillerPolicy};
+use crate::profile;
 use crate::theme;
 use crate::utils;
 
@@ -13,7 +15,7 @@ use serde::{Deserialize, Serialize};
 use serde_json;
 use std::fs::{File, OpenOptions};
 use std::io::{Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -22,7 +24,42 @@ pub struct Override {
     pub intro: bool,
     pub outro: bool,
     pub recap: bool,
-    pub filler: bool,
+    pub filler: bool, // Old override, toggles between skipping and playing
+    #[serde(default)]
+    pub recap_episodes: bool,
+    #[serde(default)]
+    pub filler_policy: Option<FillerPolicy>,
+}
+
+impl Override {
+    // The filler policy of the anime, the one in the config is used if there's no override
+    pub fn filler_policy(&self, config: &Config) -> FillerPolicy {
+        match self.filler_policy {
+            Some(policy) => policy,
+            None if self.filler => config.filler_policy.toggled(),
+            None => config.filler_policy,
+        }
+    }
+
+    // The policy to start from when the override is edited, old overrides are turned into one
+    pub fn stored_filler_policy(&self, config: &Config) -> Option<FillerPolicy> {
+        self.filler_policy.or(self.filler.then(|| config.filler_policy.toggled()))
+    }
+
+    pub fn filler_label(&self) -> String {
+        match self.filler_policy {
+            Some(policy) => policy.to_string(),
+            None if self.filler => "toggled".to_string(),
+            None => "default".to_string(),
+        }
+    }
+}
+
+// Overrides are kept per profile
+fn override_path() -> PathBuf {
+    profile::data_dir()
+        .expect("Failed to get profile directory")
+        .join("override.json")
 }
 
 pub fn read_settings_from_file(file_path: &str) -> Result<Vec<Override>> {
@@ -65,6 +102,8 @@ fn update_or_add_setting(file_path: &str, new_setting: Override) -> Result<()> {
         setting.outro = new_setting.outro;
         setting.recap = new_setting.recap;
         setting.filler = new_setting.filler;
+        setting.recap_episodes = new_setting.recap_episodes;
+        setting.filler_policy = new_setting.filler_policy;
     } else {
         settings.push(new_setting);
     }
@@ -81,17 +120,24 @@ fn find_setting_by_id(file_path: &str, id: i32) -> Option<Override> {
         .and_then(|settings| settings.into_iter().find(|setting| setting.id == id))
 }
 
-pub fn add_override(id: i32, intro: bool, outro: bool, recap: bool, filler: bool) {
-    let data_path = dirs::data_local_dir().unwrap();
-    let dir_path = data_path.join("yato");
-    let file_path = dir_path.join("override.json");
+pub fn add_override(
+    id: i32,
+    intro: bool,
+    outro: bool,
+    recap: bool,
+    recap_episodes: bool,
+    filler_policy: Option<FillerPolicy>,
+) {
+    let file_path = override_path();
 
     let new_setting = Override {
         id,
         intro,
         outro,
         recap,
-        filler,
+        filler: false,
+        recap_episodes,
+        filler_policy,
     };
 
     if let Err(e) = update_or_add_setting(file_path.to_str().unwrap(), new_setting) {
@@ -100,9 +146,7 @@ pub fn add_override(id: i32, intro: bool, outro: bool, recap: bool, filler: bool
 }
 
 pub fn search(id: i32) -> Override {
-    let data_path = dirs::data_local_dir().unwrap();
-    let dir_path = data_path.join("yato");
-    let file_path = dir_path.join("override.json");
+    let file_path = override_path();
 
     match find_setting_by_id(file_path.to_owned().to_str().unwrap(), id) {
         Some(setting) => setting,
@@ -112,14 +156,14 @@ pub fn search(id: i32) -> Override {
             outro: false,
             recap: false,
             filler: false,
+            recap_episodes: false,
+            filler_policy: None,
         },
     }
 }
 
 pub fn delete_override(id: i32) {
-    let data_path = dirs::data_local_dir().unwrap();
-    let dir_path = data_path.join("yato");
-    let file_path = dir_path.join("override.json");
+    let file_path = override_path();
 
     match read_settings_from_file(file_path.to_str().unwrap()).with_context(|| {
         format!(
@@ -153,9 +197,7 @@ pub fn delete_override(id: i32) {
 }
 
 pub async fn interactive_delete_override(client: &Client) -> Result<()> {
-    let data_path = dirs::data_local_dir().unwrap();
-    let dir_path = data_path.join("yato");
-    let file_path = dir_path.join("override.json");
+    let file_path = override_path();
 
     let settings = match read_settings_from_file(file_path.to_str().unwrap()) {
         Ok(s) if !s.is_empty() => s,
@@ -181,8 +223,8 @@ pub async fn interactive_delete_override(client: &Client) -> Result<()> {
                 Err(_) => "<Unknown Title>",
             };
             format!(
-                "{} | Current override settings: \topening: {} | credits: {} | recap: {} | filler: {}",
-                title, s.intro, s.outro, s.recap, s.filler
+                "{} | Current override settings: \topening: {} | credits: {} | recap: {} | filler: {} | recap episodes: {}",
+                title, s.intro, s.outro, s.recap, s.filler_label(), s.recap_episodes
             )
         })
         .collect();
@@ -215,17 +257,13 @@ pub async fn interactive_delete_override(client: &Client) -> Result<()> {
 }
 
 pub fn check_override() -> bool {
-    let data_path = dirs::data_local_dir().unwrap();
-    let dir_path = data_path.join("yato");
-    let file_path = dir_path.join("override.json");
+    let file_path = override_path();
 
     if file_path.exists() { true } else { false }
 }
 
 pub async fn interactive_update_override(client: &Client) -> Result<()> {
-    let data_path = dirs::data_local_dir().unwrap();
-    let dir_path = data_path.join("yato");
-    let file_path = dir_path.join("override.json");
+    let file_path = override_path();
 
     let settings = match read_settings_from_file(file_path.to_str().unwrap()) {
         Ok(s) if !s.is_empty() => s,
@@ -272,15 +310,15 @@ pub async fn interactive_update_override(client: &Client) -> Result<()> {
                 let mut intro = settings[index].intro;
                 let mut outro = settings[index].outro;
                 let mut recap = settings[index].recap;
-                let mut filler = settings[index].filler;
+                let mut recap_episodes = settings[index].recap_episodes;
 
-                let options = vec!["Intro", "Outro", "Recap", "Filler"];
+                let options = ["Intro", "Outro", "Recap", "Recap episodes"];
                 let selection = MultiSelect::with_theme(&theme)
                     .with_prompt("What overrides do you want to enable?")
-                    .item_checked(&options[0], intro)
-                    .item_checked(&options[1], outro)
-                    .item_checked(&options[2], recap)
-                    .item_checked(&options[3], filler)
+                    .item_checked(options[0], intro)
+                    .item_checked(options[1], outro)
+                    .item_checked(options[2], recap)
+                    .item_checked(options[3], recap_episodes)
                     .interact_opt()
                     .unwrap();
                 utils::clear();
@@ -289,19 +327,14 @@ pub async fn interactive_update_override(client: &Client) -> Result<()> {
                     process::exit(0);
                 } else {
                     let selected = selection.unwrap();
-                    for i in selected {
-                        if i == 0 {
-                            intro = true;
-                        } else if i == 1 {
-                            outro = true;
-                        } else if i == 2 {
-                            recap = true;
-                        }
-                        if i == 3 {
-                            filler = true;
-                        }
-                    }
-                    add_override(id_to_update, intro, outro, recap, filler);
+                    // Unchecked items are turned off
+                    intro = selected.contains(&0);
+                    outro = selected.contains(&1);
+                    recap = selected.contains(&2);
+                    recap_episodes = selected.contains(&3);
+                    let current = settings[index].stored_filler_policy(&config::load_config());
+                    let filler_policy = select_filler_policy(current)?;
+                    add_override(id_to_update, intro, outro, recap, recap_episodes, filler_policy);
                     println!("Overrides updated!");
                     Ok(())
                 }
@@ -313,3 +346,67 @@ pub async fn interactive_update_override(client: &Client) -> Result<()> {
         }
     }
 }
+
+// Lets the user pick the filler policy of an anime, None means using the one in the config
+pub fn select_filler_policy(current: Option<FillerPolicy>) -> Result<Option<FillerPolicy>> {
+    let mut options = vec!["Use the config setting".to_string()];
+    options.extend(FillerPolicy::ALL.iter().map(|p| p.to_string()));
+
+    let theme = theme::CustomTheme {};
+    let selection = Select::with_theme(&theme)
+        .with_prompt("What should happen with filler episodes?")
+        .items(&options)
+        .default(current.map_or(0, |c| {
+            FillerPolicy::ALL.iter().position(|p| *p == c).unwrap_or(0) + 1
+        }))
+        .interact_opt()?;
+    utils::clear();
+
+    match selection {
+        Some(0) => Ok(None),
+        Some(i) => Ok(Some(FillerPolicy::ALL[i - 1])),
+        None => Err(anyhow::anyhow!("No selection made")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_filler(filler: bool, filler_policy: Option<FillerPolicy>) -> Override {
+        Override {
+            filler,
+            filler_policy,
+            ..empty()
+        }
+    }
+
+    fn empty() -> Override {
+        Override {
+            id: 1,
+            intro: false,
+            outro: false,
+            recap: false,
+            filler: false,
+            recap_episodes: false,
+            filler_policy: None,
+        }
+    }
+
+    #[test]
+    fn old_filler_toggle_survives_an_edit() {
+        let config = Config::default();
+        let old = with_filler(true, None);
+        let stored = old.stored_filler_policy(&config);
+        assert_eq!(stored, Some(config.filler_policy.toggled()));
+        assert_eq!(stored.unwrap(), old.filler_policy(&config));
+    }
+
+    #[test]
+    fn explicit_policy_wins_over_the_old_toggle() {
+        let config = Config::default();
+        let setting = with_filler(true, Some(FillerPolicy::Ask));
+        assert_eq!(setting.stored_filler_policy(&config), Some(FillerPolicy::Ask));
+        assert_eq!(with_filler(false, None).stored_filler_policy(&config), None);
+    }
+}
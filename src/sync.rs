@@ -0,0 +1,200 @@
+// Two-way sync between the local progress database and AniList
+// When the two disagree, whichever was changed last wins
+// Progress is compared, and so is rewatching, the only status kept locally
+
+// =============== Imports ================
+use crate::api::anilist::{mutation, user_fetch};
+use crate::local_save::ProgressDatabase;
+
+use anyhow::{Context, Result};
+use console::style;
+use reqwest::Client;
+
+enum Action {
+    // AniList is newer, local entry moves to the episode after AniList's progress
+    Local { from: u32, to: u32 },
+    // Local is newer, AniList gets the episodes finished locally
+    Remote { from: u32, to: u32 },
+    // AniList is rewatching but local isn't, the local rewatch starts after AniList's progress
+    LocalRewatch { to: u32 },
+    // AniList stopped rewatching, the local rewatch is dropped
+    LocalEndRewatch,
+    // The local rewatch is newer, AniList is set back to rewatching
+    RemoteRewatch { from: String, to: u32 },
+}
+
+struct Conflict {
+    media_id: i32,
+    title: String,
+    rewatching: bool,
+    action: Action,
+}
+
+// Compares every local entry with AniList and fixes the differences
+// Returns how many entries were (or in a dry run would be) changed
+pub async fn run(client: &Client, dry_run: bool, quiet: bool) -> Result<usize> {
+    log::info!("Syncing local progress with AniList (dry run: {})", dry_run);
+    let mut db = ProgressDatabase::load().with_context(|| "Failed to load progress database")?;
+    let remote = user_fetch::list_states(client).await?;
+
+    let mut conflicts = Vec::new();
+    for local in &db.entries {
+        let Some(state) = remote.iter().find(|s| s.media_id == local.anilist_id) else {
+            log::info!("{} is not on the AniList list, not syncing it", local.anilist_id);
+            continue;
+        };
+
+        // * While rewatching, AniList's progress is the rewatch progress
+        let rewatching = state.status == "REPEATING";
+        let status_action = match (&local.rewatch, rewatching) {
+            (None, true) => Some(Action::LocalRewatch {
+                to: state.progress + 1,
+            }),
+            (Some(rewatch), false) if state.updated_at >= rewatch.updated_at => {
+                Some(Action::LocalEndRewatch)
+            }
+            (Some(rewatch), false) => Some(Action::RemoteRewatch {
+                from: state.status.clone(),
+                // * A special counts as the episode before it
+                to: rewatch
+                    .episode
+                    .number()
+                    .map_or(rewatch.episode.progress(), |n| n - 1),
+            }),
+            _ => None,
+        };
+        if let Some(action) = status_action {
+            conflicts.push(Conflict {
+                media_id: local.anilist_id,
+                title: state.title.clone(),
+                rewatching: true,
+                action,
+            });
+            continue;
+        }
+
+        let (episode, local_updated) = match (&local.rewatch, rewatching) {
+            (Some(rewatch), true) => (&rewatch.episode, rewatch.updated_at),
+            _ => (&local.episode, local.updated_at),
+        };
+        // * Specials don't count on AniList and 0 means nothing was watched, there's nothing to compare
+        let Some(episode) = episode.number() else {
+            continue;
+        };
+
+        // * Local episode is the one being watched, so AniList is either on it or one behind
+        let progress = state.progress;
+        if progress == episode || progress + 1 == episode {
+            continue;
+        }
+
+        let action = if state.updated_at >= local_updated {
+            Action::Local {
+                from: episode,
+                to: progress + 1,
+            }
+        } else if state.status == "COMPLETED" && episode - 1 < progress {
+            // Probably rewatching without telling AniList, a finished show is left alone
+            log::info!("{} is completed on AniList, not lowering its progress", state.title);
+            continue;
+        } else {
+            Action::Remote {
+                from: progress,
+                to: episode - 1,
+            }
+        };
+
+        conflicts.push(Conflict {
+            media_id: local.anilist_id,
+            title: state.title.clone(),
+            rewatching,
+            action,
+        });
+    }
+
+    if conflicts.is_empty() {
+        if !quiet {
+            println!("Local progress and AniList are in sync.");
+        }
+        return Ok(0);
+    }
+
+    if dry_run {
+        println!("{}", style("Sync (dry run, nothing is changed)").bold().underlined());
+    } else {
+        println!("{}", style("Sync").bold().underlined());
+    }
+
+    // * Local entries moved to AniList's state take its time, so they don't look newer next time
+    let remote_updated_at = |media_id: i32| {
+        remote
+            .iter()
+            .find(|s| s.media_id == media_id)
+            .map(|s| s.updated_at)
+            .unwrap_or(0)
+    };
+    for conflict in &conflicts {
+        match &conflict.action {
+            Action::Local { from, to } => {
+                println!(
+                    "{}: local episode {} -> {} (AniList is newer)",
+                    conflict.title, from, to
+                );
+                if !dry_run {
+                    db.set_episode(conflict.media_id, conflict.rewatching, *to, remote_updated_at(conflict.media_id));
+                }
+            }
+            Action::Remote { from, to } => {
+                println!(
+                    "{}: AniList progress {} -> {} (local is newer)",
+                    conflict.title, from, to
+                );
+                if !dry_run {
+                    mutation::update_progress(client, conflict.media_id, *to)
+                        .await
+                        .with_context(|| format!("Failed to sync {}", conflict.title))?;
+                }
+            }
+            Action::LocalRewatch { to } => {
+                println!(
+                    "{}: local rewatch starts at episode {} (rewatching on AniList)",
+                    conflict.title, to
+                );
+                if !dry_run {
+                    db.set_episode(conflict.media_id, true, *to, remote_updated_at(conflict.media_id));
+                }
+            }
+            Action::LocalEndRewatch => {
+                println!(
+                    "{}: local rewatch removed (AniList is newer and not rewatching)",
+                    conflict.title
+                );
+                if !dry_run {
+                    db.clear_rewatch(conflict.media_id);
+                }
+            }
+            Action::RemoteRewatch { from, to } => {
+                println!(
+                    "{}: AniList status {} -> rewatching at progress {} (local is newer)",
+                    conflict.title,
+                    mutation::status_label(from),
+                    to
+                );
+                if !dry_run {
+                    mutation::update_status(client, conflict.media_id, mutation::REPEATING)
+                        .await
+                        .with_context(|| format!("Failed to sync {}", conflict.title))?;
+                    mutation::update_progress(client, conflict.media_id, *to)
+                        .await
+                        .with_context(|| format!("Failed to sync {}", conflict.title))?;
+                }
+            }
+        }
+    }
+
+    if !dry_run {
+        db.save().with_context(|| "Failed to save progress database")?;
+        log::info!("Synced {} entries", conflicts.len());
+    }
+    Ok(conflicts.len())
+}
@@ -1,10 +1,15 @@
 // =============== Imports ================
+use crate::http::{self, Service};
+use crate::theme;
+
 use anyhow::{Context, Result};
+use dialoguer::FuzzySelect;
 use log;
 use reqwest::Client;
 use simplelog;
 use std::fs;
 use std::fs::File;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 
 pub fn init_log() -> Result<()> {
@@ -27,15 +32,44 @@ pub fn init_log() -> Result<()> {
     Ok(())
 }
 
+// Current time as a Unix timestamp in seconds
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
 // Clearing screen
 pub fn clear() -> () {
     let _ = console::Term::stdout().clear_screen();
 }
 
+// Lets the user pick a filter value, Some(None) clears the filter and None keeps it as it was
+pub fn pick(prompt: &str, values: &[String]) -> Result<Option<Option<String>>> {
+    let mut options = vec!["Any".to_string()];
+    options.extend(values.iter().cloned());
+    let theme = theme::CustomTheme {};
+    let selection = FuzzySelect::with_theme(&theme)
+        .with_prompt(prompt)
+        .items(&options)
+        .default(0)
+        .interact_opt()?;
+    clear();
+    Ok(selection.map(|index| (index > 0).then(|| options[index].clone())))
+}
+
+// Average score of an anime as shown in lists, e.g. "78%"
+pub fn score(average: Option<u32>) -> String {
+    average
+        .map(|s| format!("{}%", s))
+        .unwrap_or_else(|| "-".to_string())
+}
+
 // Check if network is available
 pub async fn check_network(client: &Client) -> Result<()> {
     let url = "https://www.google.com";
-    let response = client.get(url).send().await;
+    let response = http::send(Service::Other, client.get(url)).await;
     match response {
         Ok(_) => Ok(()), // Network is available
         Err(e) => {